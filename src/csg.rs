@@ -0,0 +1,144 @@
+//constructive solid geometry. combines two closed objects by walking along the ray
+//through both of their intervals and keeping track of whether it's inside each one.
+
+extern crate serde;
+use self::serde::{Deserialize, Serialize};
+
+use crate::*;
+
+const EPSILON: f64 = 0.000001;
+
+#[derive(Deserialize,Serialize,Clone,Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference, //a with b cut out of it
+}
+impl CsgOp {
+    fn inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+pub struct Csg {
+    pub op: CsgOp,
+    pub a: Box<dyn SceneObject + Send + Sync>,
+    pub b: Box<dyn SceneObject + Send + Sync>,
+}
+impl Csg {
+    pub fn new(op: CsgOp, a: Box<dyn SceneObject + Send + Sync>, b: Box<dyn SceneObject + Send + Sync>) -> Csg
+    { Csg { op, a, b } }
+}
+impl SceneObject for Csg {
+    fn as_any(&self) -> &dyn Any { self }
+    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+        //first surface in front of the ray, whether it's going in or out
+        for interval in self.intervals(ray)? {
+            if interval.enter.depth > EPSILON { return Some(interval.enter); }
+            if interval.exit.depth > EPSILON { return Some(interval.exit); }
+        }
+        None
+    }
+    fn intervals(&self, ray: Ray) -> Option<Vec<Interval>> {
+        //every boundary of either object: (hit, is b, is entering)
+        let mut events = Vec::new();
+        for interval in self.a.intervals(ray).unwrap_or_default() {
+            events.push((interval.enter, false, true));
+            events.push((interval.exit, false, false));
+        }
+        for interval in self.b.intervals(ray).unwrap_or_default() {
+            events.push((interval.enter, true, true));
+            events.push((interval.exit, true, false));
+        }
+        events.sort_by(|x, y| x.0.depth.total_cmp(&y.0.depth));
+
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter: Option<RaycastHit> = None;
+        let mut spans = Vec::new();
+        for (mut hit, is_b, entering) in events {
+            let was_inside = self.op.inside(in_a, in_b);
+            if is_b { in_b = entering; } else { in_a = entering; }
+            let inside = self.op.inside(in_a, in_b);
+
+            //the surface of b is inside out when it's being cut away
+            if is_b && matches!(self.op, CsgOp::Difference) {
+                hit.normal = -hit.normal;
+            }
+            if inside && !was_inside {
+                enter = Some(hit);
+            } else if was_inside && !inside {
+                if let Some(enter) = enter.take() {
+                    spans.push(Interval::new(enter, hit));
+                }
+            }
+        }
+        Some(spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool { (a - b).abs() < 1e-9 }
+    //two unit spheres overlapping between x = -0.5 and 0.5, a red one on the left and a blue one on the right
+    fn spheres(op: CsgOp) -> Csg {
+        let red = Material::new(Color::new(255,0,0,255), false);
+        let blue = Material::new(Color::new(0,0,255,255), false);
+        Csg::new(op, Box::new(Sphere::new(Vec3::new(-0.5,0.0,0.0), 1.0, red)), Box::new(Sphere::new(Vec3::new(0.5,0.0,0.0), 1.0, blue)))
+    }
+    fn along_x(from: f64) -> Ray { Ray::new(Vec3::new(from,0.0,0.0), Vec3::new(from + 1.0,0.0,0.0)) }
+    //depth, normal x and whether it's a's surface, for both ends of every interval
+    fn ends(csg: &Csg, ray: Ray) -> Vec<(f64, f64, bool)> {
+        csg.intervals(ray).unwrap().iter().flat_map(|i| [i.enter, i.exit])
+            .map(|hit| (hit.depth, hit.normal.x, hit.material.color.r == 255)).collect()
+    }
+    fn check(csg: &Csg, ray: Ray, expected: &[(f64, f64, bool)]) {
+        let ends = ends(csg, ray);
+        assert_eq!(ends.len(), expected.len(), "{:?}", ends);
+        for (end, expected) in ends.iter().zip(expected) {
+            assert!(close(end.0, expected.0) && close(end.1, expected.1) && end.2 == expected.2, "{:?}", ends);
+        }
+    }
+
+    #[test]
+    fn union() {
+        let csg = spheres(CsgOp::Union);
+        check(&csg, along_x(-10.0), &[(8.5, -1.0, true), (11.5, 1.0, false)]);
+        //from inside both, out through b
+        let hit = csg.raycast(along_x(0.0)).unwrap();
+        assert!(close(hit.depth, 1.5) && close(hit.normal.x, 1.0));
+    }
+
+    #[test]
+    fn intersection() {
+        let csg = spheres(CsgOp::Intersection);
+        //in through b, out through a
+        check(&csg, along_x(-10.0), &[(9.5, -1.0, false), (10.5, 1.0, true)]);
+        let hit = csg.raycast(along_x(0.0)).unwrap();
+        assert!(close(hit.depth, 0.5) && close(hit.normal.x, 1.0) && hit.material.color.r == 255);
+        //from inside a only, the first hit is going into b
+        let hit = csg.raycast(along_x(-1.0)).unwrap();
+        assert!(close(hit.depth, 0.5) && close(hit.normal.x, -1.0));
+    }
+
+    #[test]
+    fn difference() {
+        let csg = spheres(CsgOp::Difference);
+        //out of a where b starts, with b's normal turned around to face out of what's left
+        check(&csg, along_x(-10.0), &[(8.5, -1.0, true), (9.5, 1.0, false)]);
+        //from inside what's left
+        let hit = csg.raycast(along_x(-1.0)).unwrap();
+        assert!(close(hit.depth, 0.5) && close(hit.normal.x, 1.0));
+        //from the cut away part, there's nothing left ahead
+        assert!(csg.raycast(along_x(0.0)).is_none());
+        //from the other side, what's left of a starts at the far side of b
+        let back = Ray::new(Vec3::new(10.0,0.0,0.0), Vec3::new(9.0,0.0,0.0));
+        check(&csg, back, &[(10.5, 1.0, false), (11.5, -1.0, true)]);
+    }
+}
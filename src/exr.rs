@@ -0,0 +1,134 @@
+//writes openexr images, which hold any number of named float channels per pixel. only what's
+//needed to save renders is here: uncompressed scanlines of 32 bit floats or unsigned ints.
+
+pub enum Samples {
+    Uint(Vec<u32>),
+    Float(Vec<f32>),
+}
+impl Samples {
+    fn len(&self) -> usize {
+        match self { Samples::Uint(v) => v.len(), Samples::Float(v) => v.len() }
+    }
+    fn pixel_type(&self) -> i32 {
+        match self { Samples::Uint(_) => 0, Samples::Float(_) => 2 }
+    }
+}
+
+//channels are (name, one sample per pixel, row by row from the top). layers are written as
+//prefixes, like "normal.X", and plain "R", "G" and "B" are what viewers show by default
+pub fn write(width: usize, height: usize, mut channels: Vec<(String, Samples)>) -> Result<Vec<u8>, String> {
+    if width == 0 || height == 0 || width > i32::MAX as usize || height > i32::MAX as usize {
+        return Err(format!("can't write a {}x{} exr", width, height));
+    }
+    if let Some((name, _)) = channels.iter().find(|(_, samples)| samples.len() != width * height) {
+        return Err(format!("channel \'{}\' doesn't have a sample for every pixel", name));
+    }
+    //readers expect channels sorted by name
+    channels.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&20000630i32.to_le_bytes()); //magic number
+    out.extend_from_slice(&2i32.to_le_bytes()); //version 2, single part scanline file
+
+    let mut list = Vec::new();
+    for (name, samples) in &channels {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+        list.extend_from_slice(&samples.pixel_type().to_le_bytes());
+        list.extend_from_slice(&[0, 0, 0, 0]); //not perceptually linear, and reserved bytes
+        list.extend_from_slice(&1i32.to_le_bytes()); //x and y sampling
+        list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    list.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|v: &i32| v.to_le_bytes()).collect();
+    attribute(&mut out, "channels", "chlist", &list);
+    attribute(&mut out, "compression", "compression", &[0]);
+    attribute(&mut out, "dataWindow", "box2i", &window);
+    attribute(&mut out, "displayWindow", "box2i", &window);
+    attribute(&mut out, "lineOrder", "lineOrder", &[0]); //increasing y
+    attribute(&mut out, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut out, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    out.push(0); //end of the header
+
+    //every scanline is its own chunk, found through a table of where each one starts
+    let line_size = channels.len() * width * 4;
+    let table_start = out.len();
+    let first_line = table_start + height * 8;
+    for y in 0..height {
+        out.extend_from_slice(&((first_line + y * (line_size + 8)) as u64).to_le_bytes());
+    }
+    for y in 0..height {
+        out.extend_from_slice(&(y as i32).to_le_bytes());
+        out.extend_from_slice(&(line_size as i32).to_le_bytes());
+        let row = y * width..(y + 1) * width;
+        for (_, samples) in &channels {
+            match samples {
+                Samples::Uint(v) => for s in &v[row.clone()] { out.extend_from_slice(&s.to_le_bytes()); },
+                Samples::Float(v) => for s in &v[row.clone()] { out.extend_from_slice(&s.to_le_bytes()); },
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(kind.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 { u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) }
+
+    #[test]
+    fn scanlines_hold_sorted_channels() {
+        let channels = vec![
+            ("R".to_string(), Samples::Float(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0])),
+            ("B".to_string(), Samples::Float(vec![0.5; 6])),
+            ("object_id".to_string(), Samples::Uint(vec![7, 8, 9, 10, 11, 12])),
+        ];
+        let out = write(3, 2, channels).unwrap();
+        assert_eq!(u32_at(&out, 0), 20000630);
+        //channels come in name order, each with its pixel type
+        let list = out.windows(7).position(|w| w == b"chlist\0").unwrap() + 7 + 4;
+        assert!(out[list..].starts_with(b"B\0\x02\0\0\0"));
+        assert!(out[list + 18..].starts_with(b"R\0\x02\0\0\0"));
+        assert!(out[list + 36..].starts_with(b"object_id\0\0\0\0\0"));
+
+        //the offset table is right after the header, a list of (name, type, size, value) ending in a zero
+        let mut table = 8;
+        while out[table] != 0 {
+            let name_end = table + out[table..].iter().position(|b| *b == 0).unwrap();
+            let kind_end = name_end + 1 + out[name_end + 1..].iter().position(|b| *b == 0).unwrap();
+            table = kind_end + 5 + u32_at(&out, kind_end + 1) as usize;
+        }
+        table += 1;
+        let line_size = 3 * 3 * 4;
+        assert_eq!(out.len(), table + 2 * 8 + 2 * (8 + line_size));
+        for y in 0..2 {
+            let chunk = u64::from_le_bytes(out[table + y * 8..table + y * 8 + 8].try_into().unwrap()) as usize;
+            assert_eq!(u32_at(&out, chunk) as usize, y);
+            assert_eq!(u32_at(&out, chunk + 4) as usize, line_size);
+            //B for the whole line, then R, then object_id
+            let r = chunk + 8 + 3 * 4;
+            let floats: Vec<f32> = (0..3).map(|x| f32::from_bits(u32_at(&out, r + x * 4))).collect();
+            assert_eq!(floats, vec![y as f32 * 3.0, y as f32 * 3.0 + 1.0, y as f32 * 3.0 + 2.0]);
+            assert_eq!(u32_at(&out, r + 3 * 4), 7 + y as u32 * 3);
+        }
+    }
+
+    #[test]
+    fn every_channel_needs_every_pixel() {
+        let channels = vec![("R".to_string(), Samples::Float(vec![0.0; 5]))];
+        assert!(write(3, 2, channels).is_err());
+        assert!(write(0, 2, Vec::new()).is_err());
+    }
+}
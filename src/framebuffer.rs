@@ -0,0 +1,254 @@
+//a rendered image, kept as floats until it's saved, along with any extra passes (aovs) that
+//were asked for. those describe what's at each pixel instead of how it looks, for compositing
+//and for figuring out what went wrong with a render.
+
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::thread;
+
+use crate::*;
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum Aov {
+    Depth, //distance from the camera along the ray
+    Normal,
+    Position,
+    Albedo, //the surface's own color, before any lighting
+    ObjectId, //counting objects from 1, 0 for the background
+    MaterialId,
+    Shadow, //how much of the light from the scene's lights is blocked, from 0 to 1
+}
+pub const AOVS: [Aov;7] = [Aov::Depth, Aov::Normal, Aov::Position, Aov::Albedo, Aov::ObjectId, Aov::MaterialId, Aov::Shadow];
+impl Aov {
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Position => "position",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Shadow => "shadow",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Aov> {
+        AOVS.iter().copied().find(|aov| aov.name() == name)
+    }
+    //what it's called in exr files. single values are kept in an aov's r
+    fn channels(self) -> Vec<String> {
+        match self {
+            Aov::Depth => vec![format!("Z")],
+            Aov::Normal | Aov::Position => ["X", "Y", "Z"].iter().map(|c| format!("{}.{}", self.name(), c)).collect(),
+            Aov::Albedo => ["R", "G", "B"].iter().map(|c| format!("{}.{}", self.name(), c)).collect(),
+            Aov::ObjectId | Aov::MaterialId | Aov::Shadow => vec![self.name().to_string()],
+        }
+    }
+    //value for pixels where nothing was hit
+    pub fn background(self) -> Rgb {
+        match self {
+            Aov::Depth => Rgb::grey(f64::INFINITY),
+            _ => Rgb::grey(0.0),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub color: Vec<Rgb>,
+    pub depth: Vec<f64>, //infinite where nothing was hit
+    pub aovs: Vec<(Aov, Vec<Rgb>)>,
+}
+impl Framebuffer {
+    pub fn new(width: usize, height: usize, color: Vec<Rgb>, depth: Vec<f64>, aovs: Vec<(Aov, Vec<Rgb>)>) -> Framebuffer {
+        Framebuffer { width, height, color, depth, aovs }
+    }
+    pub fn aov(&self, aov: Aov) -> Option<&[Rgb]> {
+        self.aovs.iter().find(|(a, _)| *a == aov).map(|(_, values)| &values[..])
+    }
+    //this render pasted into base with its top left corner at x, y. aovs can't be, since there's
+    //nothing to paste them into
+    pub fn composite(&self, base: &Texture, x: usize, y: usize) -> Framebuffer {
+        let mut color: Vec<Rgb> = base.pixels.iter().map(|pixel| pixel.to_rgb()).collect();
+        let mut depth = vec![f64::INFINITY; base.width * base.height];
+        for row in 0..self.height {
+            let (from, to) = (row * self.width, (y + row) * base.width + x);
+            color[to..to + self.width].copy_from_slice(&self.color[from..from + self.width]);
+            depth[to..to + self.width].copy_from_slice(&self.depth[from..from + self.width]);
+        }
+        Framebuffer::new(base.width, base.height, color, depth, Vec::new())
+    }
+
+    //the color as a png, and every aov next to it as filename.aov.png
+    pub fn write_pngs(&self, path: &Path) -> Result<Vec<String>, String> {
+        let mut written = vec![path.display().to_string()];
+        self.write_png(path, &self.color)?;
+        for (aov, _) in &self.aovs {
+            let aov_path = aov_path(path, aov.name());
+            self.write_png(&aov_path, &self.preview(*aov))?;
+            written.push(aov_path.display().to_string());
+        }
+        Ok(written)
+    }
+    fn write_png(&self, path: &Path, pixels: &[Rgb]) -> Result<(), String> {
+        let error = |e: png::EncodingError| format!("can't write \'{}\': {}", path.display(), e);
+        let file = File::create(path).map_err(|e| format!("can't write \'{}\': {}", path.display(), e))?;
+        let w = &mut BufWriter::new(file);
+
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(error)?;
+
+        let mut data = Vec::with_capacity(self.width * self.height * 3);
+        for pixel in &pixels[..self.width * self.height] {
+            let color = pixel.to_color();
+            data.push(color.r);
+            data.push(color.g);
+            data.push(color.b);
+        }
+        writer.write_image_data(&data).map_err(error)
+    }
+    //the color and every aov as layers of a single exr, without clamping anything
+    pub fn write_exr(&self, path: &Path) -> Result<(), String> {
+        let pixels = self.width * self.height;
+        let float = |values: &[Rgb], channel: fn(&Rgb) -> f64| {
+            exr::Samples::Float(values[..pixels].iter().map(|v| channel(v) as f32).collect())
+        };
+        let channels: [fn(&Rgb) -> f64;3] = [|v| v.r, |v| v.g, |v| v.b];
+        let mut layers = Vec::new();
+        for (name, channel) in ["R", "G", "B"].iter().zip(channels.iter()) {
+            layers.push((name.to_string(), float(&self.color, *channel)));
+        }
+        for (aov, values) in &self.aovs {
+            for (name, channel) in aov.channels().into_iter().zip(channels.iter()) {
+                let samples = match aov {
+                    Aov::ObjectId | Aov::MaterialId => exr::Samples::Uint(values[..pixels].iter().map(|v| v.r as u32).collect()),
+                    _ => float(values, *channel),
+                };
+                layers.push((name, samples));
+            }
+        }
+        let data = exr::write(self.width, self.height, layers)?;
+        fs::write(path, data).map_err(|e| format!("can't write \'{}\': {}", path.display(), e))
+    }
+
+    //joint bilateral filter: smooths out noise by averaging each pixel with its neighbours, but
+    //only the ones that look like the same surface going by the normal, depth and albedo aovs
+    //(whichever there are), so edges stay sharp. lighting is filtered apart from the albedo,
+    //so textures don't get blurred either. the background is left alone
+    pub fn denoise(&mut self, threads: usize) {
+        const RADIUS: usize = 5; //in pixels
+        const SPATIAL: f64 = 2.5; //standard deviations of each guide's gaussian
+        const NORMAL: f64 = 0.05; //1 - cosine of the angle between normals
+        const DEPTH: f64 = 0.03; //relative to the pixel's own depth
+        const ALBEDO: f64 = 0.1;
+
+        let (width, height) = (self.width, self.height);
+        let albedo = self.aov(Aov::Albedo).map(|albedo| albedo.to_vec());
+        let normal = self.aov(Aov::Normal).map(|normal| normal.to_vec());
+        let depth = &self.depth;
+        //dividing by albedo near 0 would blow the noise up, those channels are kept as they are
+        let strip = |color: f64, albedo: f64| if albedo > 0.01 { color / albedo } else { color };
+        let light: Vec<Rgb> = match &albedo {
+            Some(albedo) => self.color.iter().zip(albedo).map(|(c, a)| {
+                Rgb::new(strip(c.r, a.r), strip(c.g, a.g), strip(c.b, a.b))
+            }).collect(),
+            None => self.color.clone(),
+        };
+
+        let mut filtered = light.clone();
+        let rows = height.div_ceil(threads.max(1));
+        thread::scope(|scope| {
+            for (band, out) in filtered.chunks_mut(rows * width).enumerate() {
+                let (light, albedo, normal) = (&light, &albedo, &normal);
+                scope.spawn(move || {
+                    for (i, pixel) in out.iter_mut().enumerate() {
+                        let p = band * rows * width + i;
+                        if !depth[p].is_finite() { continue; }
+                        let (x, y) = (p % width, p / width);
+                        let mut sum = Rgb::grey(0.0);
+                        let mut total = 0.0;
+                        for qy in y.saturating_sub(RADIUS)..(y + RADIUS + 1).min(height) {
+                            for qx in x.saturating_sub(RADIUS)..(x + RADIUS + 1).min(width) {
+                                let q = qy * width + qx;
+                                if !depth[q].is_finite() { continue; }
+                                let (dx, dy) = (qx as f64 - x as f64, qy as f64 - y as f64);
+                                let mut exponent = (dx * dx + dy * dy) / (2.0 * SPATIAL * SPATIAL);
+                                let relative = (depth[q] - depth[p]) / depth[p].max(1e-9);
+                                exponent += relative * relative / (2.0 * DEPTH * DEPTH);
+                                if let Some(normal) = normal {
+                                    let (a, b) = (normal[p], normal[q]);
+                                    exponent += (1.0 - (a.r * b.r + a.g * b.g + a.b * b.b)).max(0.0) / NORMAL;
+                                }
+                                if let Some(albedo) = albedo {
+                                    let d = albedo[q] + albedo[p] * -1.0;
+                                    exponent += (d.r * d.r + d.g * d.g + d.b * d.b) / (2.0 * ALBEDO * ALBEDO);
+                                }
+                                let weight = (-exponent).exp();
+                                sum = sum + light[q] * weight;
+                                total += weight;
+                            }
+                        }
+                        if total > 0.0 { *pixel = sum / total; }
+                    }
+                });
+            }
+        });
+
+        let color = match &albedo {
+            Some(albedo) => filtered.iter().zip(albedo).map(|(l, a)| {
+                let put_back = |light: f64, albedo: f64| if albedo > 0.01 { light * albedo } else { light };
+                Rgb::new(put_back(l.r, a.r), put_back(l.g, a.g), put_back(l.b, a.b))
+            }).collect(),
+            None => filtered,
+        };
+        self.color = color;
+    }
+
+    //an aov as something that can be looked at: depths from white up close to black far away,
+    //normals and positions as colors, and ids as a different random color for each
+    pub fn preview(&self, aov: Aov) -> Vec<Rgb> {
+        let values = match self.aov(aov) { Some(values) => values, None => { return Vec::new(); } };
+        let hits = || values.iter().zip(&self.depth).filter(|(_, depth)| depth.is_finite()).map(|(v, _)| *v);
+        match aov {
+            Aov::Depth => {
+                let near = hits().map(|v| v.r).fold(f64::INFINITY, f64::min);
+                let far = hits().map(|v| v.r).fold(0.0, f64::max);
+                values.iter().map(|v| {
+                    if !v.r.is_finite() { return Rgb::grey(0.0); }
+                    Rgb::grey(if far > near { 1.0 - (v.r - near) / (far - near) * 0.9 } else { 1.0 })
+                }).collect()
+            }
+            Aov::Normal => values.iter().map(|v| *v * 0.5 + Rgb::grey(0.5)).collect(),
+            Aov::Position => {
+                let low = hits().fold(Rgb::grey(f64::INFINITY), |l, v| Rgb::new(l.r.min(v.r), l.g.min(v.g), l.b.min(v.b)));
+                let high = hits().fold(Rgb::grey(f64::NEG_INFINITY), |h, v| Rgb::new(h.r.max(v.r), h.g.max(v.g), h.b.max(v.b)));
+                let scale = |v: f64, low: f64, high: f64| if high > low { (v - low) / (high - low) } else { 0.5 };
+                values.iter().zip(&self.depth).map(|(v, depth)| {
+                    if !depth.is_finite() { return Rgb::grey(0.0); }
+                    Rgb::new(scale(v.r, low.r, high.r), scale(v.g, low.g, high.g), scale(v.b, low.b, high.b))
+                }).collect()
+            }
+            Aov::ObjectId | Aov::MaterialId => values.iter().map(|v| id_color(v.r as u32)).collect(),
+            Aov::Albedo | Aov::Shadow => values.to_vec(),
+        }
+    }
+}
+
+//render.png with the aov depth is render.depth.png
+fn aov_path(path: &Path, aov: &str) -> std::path::PathBuf {
+    let stem = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let extension = path.extension().map_or(String::from("png"), |e| e.to_string_lossy().into_owned());
+    path.with_file_name(format!("{}.{}.{}", stem, aov, extension))
+}
+
+//bright, easy to tell apart colors for ids. 0 is black
+fn id_color(id: u32) -> Rgb {
+    if id == 0 { return Rgb::grey(0.0); }
+    let mut rng = Rng::new(id as u64);
+    Rgb::new(0.2 + rng.next_f64() * 0.8, 0.2 + rng.next_f64() * 0.8, 0.2 + rng.next_f64() * 0.8)
+}
@@ -0,0 +1,991 @@
+//loaders for mesh files. whatever the format, everything in the file ends up in a single
+//mesh, with offset and scale applied to positions as they're read.
+
+use std::fs;
+use std::path::Path;
+use std::collections::HashMap;
+
+use crate::*;
+
+//reads an obj file into a single mesh. faces with more than three corners are split into
+//a fan of tris. with use_mtl, materials come from the obj's mtl library where it can be
+//found, otherwise everything gets the given material.
+pub fn read_obj(contents: &str, path: &Path, offset: Vec3, scale: Vec3, material: Material, use_mtl: bool)
+-> Result<Mesh, String> {
+    let mut verts: Vec<Vec3> = Vec::new();
+    let mut norms: Vec<Vec3> = Vec::new();
+    let mut tex_coords: Vec<[f32;2]> = Vec::new();
+
+    //obj indexes positions, normals and uvs separately, but a mesh needs one index per vertex,
+    //so every distinct combination that shows up becomes a vertex
+    let mut vertex_ids: HashMap<(usize,Option<usize>,Option<usize>), u32> = HashMap::new();
+    let mut corners: Vec<(usize,Option<usize>,Option<usize>)> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    let mut library: HashMap<String, Material> = HashMap::new();
+    let mut materials = vec![material];
+    let mut material_ids: HashMap<String, u16> = HashMap::new();
+    let mut current_material: u16 = 0;
+    let mut tri_materials: Vec<u16> = Vec::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let error = |what: &str| format!("line {}: {}", line_number + 1, what);
+        let mut values = line.split_whitespace();
+        let number = |value: Option<&str>| -> Result<f64,String> {
+            value.ok_or_else(|| error("missing value"))?.parse::<f64>().map_err(|_| error("invalid number"))
+        };
+        match values.next() {
+            Some("v") => {
+                verts.push(Vec3::new(number(values.next())?, number(values.next())?, number(values.next())?));
+            }
+            Some("vn") => {
+                norms.push(Vec3::new(number(values.next())?, number(values.next())?, number(values.next())?));
+            }
+            Some("vt") => {
+                //obj uvs start from the bottom, textures are sampled from the top
+                tex_coords.push([number(values.next())? as f32, 1.0 - number(values.next())? as f32]);
+            }
+            Some("f") => {
+                let mut face = Vec::new();
+                for value in values {
+                    //v, v/vt, v//vn or v/vt/vn. negative indices count back from the end
+                    let mut parts = value.split('/');
+                    let mut index = |len: usize| -> Result<Option<usize>,String> {
+                        match parts.next() {
+                            None | Some("") => Ok(None),
+                            Some(part) => {
+                                let i: i64 = part.parse().map_err(|_| error("invalid index"))?;
+                                let i = if i < 0 { len as i64 + i } else { i - 1 };
+                                if i < 0 || i as usize >= len { return Err(error("index out of range")); }
+                                Ok(Some(i as usize))
+                            }
+                        }
+                    };
+                    let v = index(verts.len())?.ok_or_else(|| error("face is missing a vertex index"))?;
+                    let vt = index(tex_coords.len())?;
+                    let vn = index(norms.len())?;
+                    let key = (v, vt, vn);
+                    let id = *vertex_ids.entry(key).or_insert_with(|| {
+                        corners.push(key);
+                        (corners.len() - 1) as u32
+                    });
+                    face.push(id);
+                }
+                if face.len() < 3 { return Err(error("face needs at least three vertices")); }
+                for i in 1..face.len() - 1 {
+                    indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                    tri_materials.push(current_material);
+                }
+            }
+            Some("mtllib") if use_mtl => {
+                //missing libraries aren't fatal, the default material is used instead
+                for name in values {
+                    if let Ok(mtl) = fs::read_to_string(path.parent().unwrap().join(name)) {
+                        library.extend(read_mtl(&mtl, material));
+                    }
+                }
+            }
+            Some("usemtl") if use_mtl => {
+                let name = values.next().unwrap_or("").to_string();
+                current_material = match (material_ids.get(&name), library.get(&name)) {
+                    (Some(&id), _) => id,
+                    (None, Some(&found)) => {
+                        materials.push(found);
+                        let id = (materials.len() - 1) as u16;
+                        material_ids.insert(name, id);
+                        id
+                    }
+                    (None, None) => 0,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    let mut positions = Vec::with_capacity(corners.len());
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    //normals and uvs are all or nothing, if any vertex is missing one they're ignored
+    let has_normals = corners.iter().all(|c| c.2.is_some());
+    let has_uvs = corners.iter().all(|c| c.1.is_some());
+    for &(v, vt, vn) in &corners {
+        let p = verts[v] * scale + offset;
+        positions.push([p.x as f32, p.y as f32, p.z as f32]);
+        if has_normals {
+            //normals squash the opposite way to positions under uneven scaling
+            let n = (norms[vn.unwrap()] / scale).unit();
+            normals.push([n.x as f32, n.y as f32, n.z as f32]);
+        }
+        if has_uvs {
+            uvs.push(tex_coords[vt.unwrap()]);
+        }
+    }
+    Ok(Mesh::new(positions, normals, uvs, indices, materials, Vec::new(), tri_materials))
+}
+
+//materials from an mtl file by name. only the diffuse and specular colors, shininess,
+//dissolve, and whether it's a mirror (illumination models 3 and up are the raytraced reflection ones) are used
+fn read_mtl(contents: &str, default: Material) -> HashMap<String, Material> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+    for line in contents.lines() {
+        let mut values = line.split_whitespace();
+        match (values.next(), current.as_mut()) {
+            (Some("newmtl"), _) => {
+                if let Some((name, material)) = current.take() { materials.insert(name, material); }
+                current = Some((values.next().unwrap_or("").to_string(), default));
+            }
+            (Some(key @ ("Kd" | "Ks")), Some((_, material))) => {
+                let mut channel = || {
+                    let value: f64 = values.next().and_then(|v| v.parse().ok()).unwrap_or(0.0);
+                    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+                };
+                let color = Color::new(channel(), channel(), channel(), 255);
+                if key == "Kd" { material.color = color; } else { material.specular = color; }
+            }
+            (Some("Ns"), Some((_, material))) => {
+                let shininess: f64 = values.next().and_then(|v| v.parse().ok()).unwrap_or(0.0);
+                material.roughness = Material::shininess_roughness(shininess);
+            }
+            //d is how opaque it is, Tr the opposite
+            (Some(key @ ("d" | "Tr")), Some((_, material))) => {
+                if let Some(value) = values.next().and_then(|v| v.parse::<f64>().ok()) {
+                    let value = value.clamp(0.0, 1.0);
+                    material.transparency = if key == "d" { 1.0 - value } else { value };
+                }
+            }
+            (Some("illum"), Some((_, material))) => {
+                let model: u32 = values.next().and_then(|v| v.parse().ok()).unwrap_or(0);
+                material.reflective = model >= 3;
+            }
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current { materials.insert(name, material); }
+    materials
+}
+
+fn to_f32(v: Vec3) -> [f32;3] {
+    [v.x as f32, v.y as f32, v.z as f32]
+}
+
+
+//ply scalar types
+#[derive(Clone,Copy)]
+enum PlyType { I8, U8, I16, U16, I32, U32, F32, F64 }
+impl PlyType {
+    fn parse(name: &str) -> Result<PlyType, String> {
+        Ok(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => { return Err(format!("unknown property type \'{}\'", name)); }
+        })
+    }
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+}
+enum PlyProperty {
+    Scalar(String, PlyType),
+    List(String, PlyType, PlyType), //type of the length, then of the items
+}
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+//reads values from the body of a ply file, which is either whitespace separated text or
+//packed binary in either byte order
+enum PlyBody<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { data: &'a [u8], pos: usize, big_endian: bool },
+}
+impl<'a> PlyBody<'a> {
+    fn read(&mut self, ty: PlyType) -> Result<f64, String> {
+        match self {
+            PlyBody::Ascii(tokens) => {
+                let token = tokens.next().ok_or("unexpected end of file".to_string())?;
+                token.parse().map_err(|_| format!("invalid number \'{}\'", token))
+            }
+            PlyBody::Binary { data, pos, big_endian } => {
+                let bytes = data.get(*pos..*pos + ty.size()).ok_or("unexpected end of file".to_string())?;
+                *pos += ty.size();
+                let mut b = [0u8;8];
+                b[..bytes.len()].copy_from_slice(bytes);
+                if *big_endian { b[..bytes.len()].reverse(); }
+                Ok(match ty {
+                    PlyType::I8 => b[0] as i8 as f64,
+                    PlyType::U8 => b[0] as f64,
+                    PlyType::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    PlyType::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    PlyType::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    PlyType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    PlyType::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    PlyType::F64 => f64::from_le_bytes(b),
+                })
+            }
+        }
+    }
+}
+
+//reads an ascii or binary ply file. vertex positions, normals and uvs are used, along with
+//faces, which are split into fans of tris. anything else in the file is skipped over.
+pub fn read_ply(data: &[u8], offset: Vec3, scale: Vec3, material: Material) -> Result<Mesh, String> {
+    const END: &[u8] = b"end_header";
+    let header_end = data.windows(END.len()).position(|w| w == END)
+        .ok_or("missing end_header".to_string())?;
+    let header = std::str::from_utf8(&data[..header_end]).map_err(|_| "invalid header".to_string())?;
+    let mut body_start = header_end + END.len();
+    if data.get(body_start) == Some(&b'\r') { body_start += 1; }
+    body_start += 1; //newline
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") { return Err("not a ply file".to_string()); }
+    let mut format = "";
+    let mut elements: Vec<PlyElement> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", f, ..] => { format = f; }
+            ["element", name, count] => {
+                elements.push(PlyElement {
+                    name: name.to_string(),
+                    count: count.parse().map_err(|_| format!("invalid element count \'{}\'", count))?,
+                    properties: Vec::new(),
+                });
+            }
+            ["property", "list", len, item, name] => {
+                elements.last_mut().ok_or("property before any element".to_string())?
+                    .properties.push(PlyProperty::List(name.to_string(), PlyType::parse(len)?, PlyType::parse(item)?));
+            }
+            ["property", ty, name] => {
+                elements.last_mut().ok_or("property before any element".to_string())?
+                    .properties.push(PlyProperty::Scalar(name.to_string(), PlyType::parse(ty)?));
+            }
+            _ => {} //comments, obj_info
+        }
+    }
+    let body_bytes = data.get(body_start..).unwrap_or(&[]);
+    let mut body = match format {
+        "ascii" => PlyBody::Ascii(
+            std::str::from_utf8(body_bytes).map_err(|_| "invalid ascii data".to_string())?.split_ascii_whitespace()
+        ),
+        "binary_little_endian" => PlyBody::Binary { data: body_bytes, pos: 0, big_endian: false },
+        "binary_big_endian" => PlyBody::Binary { data: body_bytes, pos: 0, big_endian: true },
+        _ => { return Err(format!("unknown format \'{}\'", format)); }
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    for element in &elements {
+        let names: Vec<&str> = element.properties.iter().map(|p| match p {
+            PlyProperty::Scalar(name, _) | PlyProperty::List(name, _, _) => name.as_str()
+        }).collect();
+        let find = |options: &[&str]| names.iter().position(|n| options.contains(n));
+        let (x, y, z) = (find(&["x"]), find(&["y"]), find(&["z"]));
+        let (nx, ny, nz) = (find(&["nx"]), find(&["ny"]), find(&["nz"]));
+        let u = find(&["u", "s", "texture_u", "texture_s"]);
+        let v = find(&["v", "t", "texture_v", "texture_t"]);
+        let face_indices = find(&["vertex_indices", "vertex_index"]);
+
+        let mut values = vec![0.0; element.properties.len()];
+        for _ in 0..element.count {
+            let mut list = Vec::new();
+            for (i, property) in element.properties.iter().enumerate() {
+                match property {
+                    PlyProperty::Scalar(_, ty) => { values[i] = body.read(*ty)?; }
+                    PlyProperty::List(_, len, item) => {
+                        let len = body.read(*len)? as usize;
+                        let mut items = Vec::new();
+                        for _ in 0..len { items.push(body.read(*item)?); }
+                        if Some(i) == face_indices { list = items; }
+                    }
+                }
+            }
+            if element.name == "vertex" {
+                let get = |i: Option<usize>| i.map_or(0.0, |i| values[i]);
+                positions.push(to_f32(Vec3::new(get(x), get(y), get(z)) * scale + offset));
+                if let (Some(_), Some(_), Some(_)) = (nx, ny, nz) {
+                    normals.push(to_f32((Vec3::new(get(nx), get(ny), get(nz)) / scale).unit()));
+                }
+                if let (Some(_), Some(_)) = (u, v) {
+                    //ply uvs start at the bottom like obj, textures are sampled from the top
+                    uvs.push([get(u) as f32, 1.0 - get(v) as f32]);
+                }
+            } else if element.name == "face" {
+                for i in 1..list.len().saturating_sub(1) {
+                    indices.extend_from_slice(&[list[0] as u32, list[i] as u32, list[i + 1] as u32]);
+                }
+            }
+        }
+    }
+    if indices.iter().any(|&i| i as usize >= positions.len()) {
+        return Err("face index out of range".to_string());
+    }
+    let tris = indices.len() / 3;
+    Ok(Mesh::new(positions, normals, uvs, indices, vec![material], Vec::new(), vec![0; tris]))
+}
+
+
+//reads an ascii or binary stl file. stl repeats every vertex for each triangle it's part of,
+//so identical positions are merged back together.
+pub fn read_stl(data: &[u8], offset: Vec3, scale: Vec3, material: Material) -> Result<Mesh, String> {
+    let mut corners: Vec<[f32;3]> = Vec::new();
+
+    //binary files start with an 80 byte header and a triangle count, then 50 bytes per triangle.
+    //ascii files should start with "solid", but so do plenty of binary ones
+    let binary_count = data.get(80..84).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    if binary_count.is_some_and(|count| 84 + count * 50 == data.len()) {
+        for tri in data[84..].chunks_exact(50) {
+            for corner in 0..3 {
+                let at = 12 + corner * 12;
+                let f = |i: usize| f32::from_le_bytes([tri[at+i], tri[at+i+1], tri[at+i+2], tri[at+i+3]]);
+                corners.push([f(0), f(4), f(8)]);
+            }
+        }
+    } else {
+        let text = std::str::from_utf8(data).map_err(|_| "not a valid stl file".to_string())?;
+        if !text.trim_start().starts_with("solid") { return Err("not a valid stl file".to_string()); }
+        for (line_number, line) in text.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            if words.next() == Some("vertex") {
+                let mut number = || -> Result<f32,String> {
+                    words.next().and_then(|w| w.parse().ok())
+                        .ok_or(format!("line {}: invalid vertex", line_number + 1))
+                };
+                corners.push([number()?, number()?, number()?]);
+            }
+        }
+        if !corners.len().is_multiple_of(3) { return Err("facets need exactly three vertices".to_string()); }
+    }
+
+    let mut ids: HashMap<[u32;3], u32> = HashMap::new();
+    let mut positions = Vec::new();
+    let mut indices = Vec::with_capacity(corners.len());
+    for corner in corners {
+        let key = [corner[0].to_bits(), corner[1].to_bits(), corner[2].to_bits()];
+        let id = *ids.entry(key).or_insert_with(|| {
+            let p = Vec3::new(corner[0] as f64, corner[1] as f64, corner[2] as f64);
+            positions.push(to_f32(p * scale + offset));
+            (positions.len() - 1) as u32
+        });
+        indices.push(id);
+    }
+    //stl normals are per face anyway, so they're left for the mesh to work out
+    let tris = indices.len() / 3;
+    Ok(Mesh::new(positions, Vec::new(), Vec::new(), indices, vec![material], Vec::new(), vec![0; tris]))
+}
+
+
+//most values a gltf accessor without any data can stand for, a vec4 for each of 4 million vertices
+const MAX_ZEROED_VALUES: usize = 1 << 24;
+//column major 4x4 matrix, for gltf node transforms
+type Matrix4 = [f64;16];
+const IDENTITY4: Matrix4 = [1.0,0.0,0.0,0.0, 0.0,1.0,0.0,0.0, 0.0,0.0,1.0,0.0, 0.0,0.0,0.0,1.0];
+
+fn mul4(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    let mut out = [0.0;16];
+    for col in 0..4 {
+        for row in 0..4 {
+            out[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+    out
+}
+fn transform_point(m: &Matrix4, p: Vec3) -> Vec3 {
+    Vec3::new(
+        m[0] * p.x + m[4] * p.y + m[8] * p.z + m[12],
+        m[1] * p.x + m[5] * p.y + m[9] * p.z + m[13],
+        m[2] * p.x + m[6] * p.y + m[10] * p.z + m[14],
+    )
+}
+//upper 3x3 of the matrix as columns
+fn linear_part(m: &Matrix4) -> Matrix3 {
+    Matrix3::new(Vec3::new(m[0], m[1], m[2]), Vec3::new(m[4], m[5], m[6]), Vec3::new(m[8], m[9], m[10]))
+}
+
+//base64 from data uris
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            b' ' | b'\n' | b'\r' | b'\t' => continue,
+            _ => { return Err("invalid base64 data".to_string()); }
+        };
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Ok(out)
+}
+
+//any png as 8 bit colors
+pub fn read_png(bytes: &[u8]) -> Result<Texture, String> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|error| format!("{}", error))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|error| format!("{}", error))?;
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        _ => 4,
+    };
+    let pixels = buffer[..info.buffer_size()].chunks_exact(channels).map(|p| match channels {
+        1 => Color::new(p[0], p[0], p[0], 255),
+        2 => Color::new(p[0], p[0], p[0], p[1]),
+        3 => Color::new(p[0], p[1], p[2], 255),
+        _ => Color::new(p[0], p[1], p[2], p[3]),
+    }).collect();
+    Ok(Texture::new(info.width as usize, info.height as usize, pixels))
+}
+
+//everything needed while reading a gltf file, to save passing it all around
+struct Gltf<'a> {
+    json: serde_json::Value,
+    buffers: Vec<Vec<u8>>,
+    path: &'a Path,
+}
+impl<'a> Gltf<'a> {
+    //bytes referenced by a uri, either embedded in it or in a file next to the gltf
+    fn load_uri(&self, uri: &str) -> Result<Vec<u8>, String> {
+        if uri.starts_with("data:") {
+            let data = uri.split(',').nth(1).ok_or("invalid data uri".to_string())?;
+            decode_base64(data)
+        } else {
+            let file = self.path.parent().unwrap().join(uri);
+            fs::read(&file).map_err(|error| format!("error reading \'{}\': {}", uri, error))
+        }
+    }
+    fn buffer_view(&self, index: u64) -> Result<&[u8], String> {
+        let view = &self.json["bufferViews"][index as usize];
+        let buffer = view["buffer"].as_u64().and_then(|b| self.buffers.get(b as usize))
+            .ok_or(format!("buffer view {} has an invalid buffer", index))?;
+        let start = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let length = view["byteLength"].as_u64().unwrap_or(0) as usize;
+        buffer.get(start..start + length).ok_or(format!("buffer view {} is out of bounds", index))
+    }
+    //every value of an accessor, flattened, along with how many components each element has
+    fn accessor(&self, index: u64) -> Result<(Vec<f64>, usize), String> {
+        let accessor = &self.json["accessors"][index as usize];
+        let count = accessor["count"].as_u64().ok_or(format!("accessor {} has no count", index))? as usize;
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1, Some("VEC2") => 2, Some("VEC3") => 3, Some("VEC4") => 4,
+            _ => { return Err(format!("accessor {} has an unsupported type", index)); }
+        };
+        if accessor.get("sparse").is_some() {
+            return Err("sparse accessors aren't supported".to_string());
+        }
+        let component_type = accessor["componentType"].as_u64().unwrap_or(0);
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => { return Err(format!("accessor {} has an unknown component type", index)); }
+        };
+        let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+
+        //accessors without a buffer view are all zeros. there's no data to check count against,
+        //so it's held to what a mesh could sensibly have
+        let view_index = match accessor["bufferView"].as_u64() {
+            Some(view) => view,
+            None => {
+                let values = count.checked_mul(components).filter(|values| *values <= MAX_ZEROED_VALUES)
+                    .ok_or_else(|| format!("accessor {} has too many elements", index))?;
+                return Ok((vec![0.0; values], components));
+            }
+        };
+        let data = self.buffer_view(view_index)?;
+        //a stride of 0 (or none) means the elements are packed right after each other
+        let stride = self.json["bufferViews"][view_index as usize]["byteStride"].as_u64()
+            .map_or(0, |s| s as usize);
+        let stride = if stride == 0 { size * components } else { stride };
+        if stride < size * components {
+            return Err(format!("accessor {} has elements overlapping each other", index));
+        }
+        let start = accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+
+        //check the last element fits before trusting count enough to allocate for it
+        let end = count.checked_sub(1).map(|last| {
+            last.checked_mul(stride).and_then(|offset| offset.checked_add(start))
+                .and_then(|at| at.checked_add(components * size))
+        });
+        if end.is_some_and(|end| end.is_none_or(|end| end > data.len())) {
+            return Err(format!("accessor {} is out of bounds", index));
+        }
+        let mut values = Vec::with_capacity(count * components);
+        for i in 0..count {
+            for c in 0..components {
+                let at = start + i * stride + c * size;
+                let b = data.get(at..at + size).ok_or_else(|| format!("accessor {} is out of bounds", index))?;
+                let value = match component_type {
+                    5120 => b[0] as i8 as f64,
+                    5121 => b[0] as f64,
+                    5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                };
+                values.push(if normalized && component_type != 5126 {
+                    match component_type {
+                        5120 => (value / 127.0).max(-1.0),
+                        5121 => value / 255.0,
+                        5122 => (value / 32767.0).max(-1.0),
+                        _ => value / 65535.0,
+                    }
+                } else {
+                    value
+                });
+            }
+        }
+        Ok((values, components))
+    }
+    fn node_matrix(&self, node: &serde_json::Value) -> Matrix4 {
+        if let Some(matrix) = node["matrix"].as_array() {
+            let mut m = IDENTITY4;
+            for (i, value) in matrix.iter().take(16).enumerate() { m[i] = value.as_f64().unwrap_or(0.0); }
+            return m;
+        }
+        let get = |key: &str, i: usize, default: f64| node[key][i].as_f64().unwrap_or(default);
+        let (tx, ty, tz) = (get("translation",0,0.0), get("translation",1,0.0), get("translation",2,0.0));
+        let (x, y, z, w) = (get("rotation",0,0.0), get("rotation",1,0.0), get("rotation",2,0.0), get("rotation",3,1.0));
+        let (sx, sy, sz) = (get("scale",0,1.0), get("scale",1,1.0), get("scale",2,1.0));
+        //translation * rotation * scale, with the rotation from a unit quaternion
+        [
+            (1.0 - 2.0 * (y * y + z * z)) * sx, 2.0 * (x * y + z * w) * sx, 2.0 * (x * z - y * w) * sx, 0.0,
+            2.0 * (x * y - z * w) * sy, (1.0 - 2.0 * (x * x + z * z)) * sy, 2.0 * (y * z + x * w) * sy, 0.0,
+            2.0 * (x * z + y * w) * sz, 2.0 * (y * z - x * w) * sz, (1.0 - 2.0 * (x * x + y * y)) * sz, 0.0,
+            tx, ty, tz, 1.0,
+        ]
+    }
+    //materials and their color textures. only the base color and metalness of the
+    //metallic-roughness model are used, metallic materials become mirrors. blended materials
+    //are as transparent as their base color's alpha says
+    fn materials(&self, default: Material) -> (Vec<Material>, Vec<Option<Texture>>) {
+        let mut images: HashMap<u64, Option<Texture>> = HashMap::new();
+        let mut materials = vec![default];
+        let mut textures = vec![None];
+        for material in self.json["materials"].as_array().into_iter().flatten() {
+            let pbr = &material["pbrMetallicRoughness"];
+            let factor = |i: usize| pbr["baseColorFactor"][i].as_f64().unwrap_or(1.0).clamp(0.0, 1.0);
+            let to_u8 = |f: f64| (f * 255.0).round() as u8;
+            let color = Color::new(to_u8(factor(0)), to_u8(factor(1)), to_u8(factor(2)), to_u8(factor(3)));
+            let metallic = pbr["metallicFactor"].as_f64().unwrap_or(1.0);
+            let transparency = if material["alphaMode"].as_str() == Some("BLEND") { 1.0 - factor(3) } else { 0.0 };
+            materials.push(Material::new(color, metallic >= 0.5).with_transparency(transparency));
+
+            let source = pbr["baseColorTexture"]["index"].as_u64()
+                .and_then(|t| self.json["textures"][t as usize]["source"].as_u64());
+            //materials can share an image, so each one gets its own copy of it
+            let texture = source.and_then(|image| {
+                images.entry(image).or_insert_with(|| match self.image(image) {
+                    Ok(texture) => Some(texture),
+                    Err(error) => {
+                        print_loud(format!("warning: skipping texture {}: {}\n", image, error));
+                        None
+                    }
+                }).clone()
+            });
+            textures.push(texture);
+        }
+        (materials, textures)
+    }
+    fn image(&self, index: u64) -> Result<Texture, String> {
+        let image = &self.json["images"][index as usize];
+        let bytes = match (image["uri"].as_str(), image["bufferView"].as_u64()) {
+            (Some(uri), _) => self.load_uri(uri)?,
+            (None, Some(view)) => self.buffer_view(view)?.to_vec(),
+            _ => { return Err("image has no data".to_string()); }
+        };
+        if !bytes.starts_with(b"\x89PNG") {
+            return Err("only png textures are supported".to_string());
+        }
+        read_png(&bytes)
+    }
+}
+
+//reads a gltf 2.0 file, either json (.gltf) or binary (.glb). every mesh in the default scene
+//is placed according to its node's transform and merged into one.
+pub fn read_gltf(data: &[u8], path: &Path, offset: Vec3, scale: Vec3, material: Material) -> Result<Mesh, String> {
+    let mut buffers = Vec::new();
+    let json_bytes = if data.starts_with(b"glTF") {
+        //glb: 12 byte header, then chunks of (length, type, data). the first is the json,
+        //the second, if any, is the binary buffer
+        let mut chunks = Vec::new();
+        let mut at = 12;
+        while at + 8 <= data.len() {
+            let length = u32::from_le_bytes([data[at], data[at+1], data[at+2], data[at+3]]) as usize;
+            let chunk = data.get(at + 8..at + 8 + length).ok_or("truncated glb chunk".to_string())?;
+            chunks.push(chunk);
+            at += 8 + length;
+        }
+        if chunks.len() > 1 { buffers.push(chunks[1].to_vec()); }
+        *chunks.first().ok_or("glb has no json chunk".to_string())?
+    } else {
+        data
+    };
+    let json: serde_json::Value = serde_json::from_slice(json_bytes)
+        .map_err(|error| format!("invalid json: {}", error))?;
+    let mut gltf = Gltf { json, buffers: Vec::new(), path };
+
+    let embedded = buffers.pop();
+    for (i, buffer) in gltf.json["buffers"].as_array().into_iter().flatten().enumerate() {
+        let bytes = match (buffer["uri"].as_str(), i) {
+            (Some(uri), _) => gltf.load_uri(uri)?,
+            (None, 0) => embedded.clone().ok_or("buffer 0 has no data".to_string())?,
+            _ => { return Err(format!("buffer {} has no data", i)); }
+        };
+        gltf.buffers.push(bytes);
+    }
+    let (materials, textures) = gltf.materials(material);
+
+    //walk the node hierarchy from the roots of the default scene, accumulating transforms
+    let nodes = gltf.json["nodes"].as_array().cloned().unwrap_or_default();
+    let scene = gltf.json["scene"].as_u64().unwrap_or(0) as usize;
+    let mut stack: Vec<(u64, Matrix4)> = match gltf.json["scenes"][scene]["nodes"].as_array() {
+        Some(roots) => roots.iter().filter_map(|n| n.as_u64()).map(|n| (n, IDENTITY4)).collect(),
+        None => {
+            //no scenes, so use every node that isn't anyone's child
+            let children: Vec<u64> = nodes.iter()
+                .flat_map(|n| n["children"].as_array().cloned().unwrap_or_default())
+                .filter_map(|c| c.as_u64()).collect();
+            (0..nodes.len() as u64).filter(|n| !children.contains(n)).map(|n| (n, IDENTITY4)).collect()
+        }
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    let mut tri_materials = Vec::new();
+    let (mut has_normals, mut has_uvs) = (true, true);
+    let mut visited = vec![false; nodes.len()];
+    while let Some((index, parent)) = stack.pop() {
+        let node = nodes.get(index as usize).ok_or(format!("node {} doesn't exist", index))?;
+        if visited[index as usize] { continue; }
+        visited[index as usize] = true;
+        let matrix = mul4(&parent, &gltf.node_matrix(node));
+        for child in node["children"].as_array().into_iter().flatten().filter_map(|c| c.as_u64()) {
+            stack.push((child, matrix));
+        }
+        let mesh = match node["mesh"].as_u64() {
+            Some(mesh) => &gltf.json["meshes"][mesh as usize],
+            None => continue,
+        };
+
+        let linear = linear_part(&matrix);
+        //normals transform by the inverse transpose, which is the cofactor matrix up to scale
+        let cofactor = Matrix3::new(
+            linear.b.cross(linear.c), linear.c.cross(linear.a), linear.a.cross(linear.b)
+        );
+        //mirroring transforms turn triangles inside out, so their winding has to be flipped back
+        let mirrored = linear.a.dot(linear.b.cross(linear.c)) < 0.0;
+
+        for primitive in mesh["primitives"].as_array().into_iter().flatten() {
+            let mode = primitive["mode"].as_u64().unwrap_or(4);
+            if !(4..=6).contains(&mode) { continue; } //points and lines
+            let attributes = &primitive["attributes"];
+            let position_accessor = match attributes["POSITION"].as_u64() {
+                Some(a) => a,
+                None => continue,
+            };
+            let (points, _) = gltf.accessor(position_accessor)?;
+            let first = positions.len() as u32;
+            let count = points.len() / 3;
+            for p in points.chunks_exact(3) {
+                positions.push(to_f32(transform_point(&matrix, Vec3::new(p[0], p[1], p[2])) * scale + offset));
+            }
+            match attributes["NORMAL"].as_u64() {
+                Some(a) => {
+                    for n in gltf.accessor(a)?.0.chunks_exact(3) {
+                        let mut normal = cofactor * Vec3::new(n[0], n[1], n[2]);
+                        if mirrored { normal = -normal; } //the cofactor matrix is flipped along with them
+                        normals.push(to_f32((normal / scale).unit()));
+                    }
+                }
+                None => { has_normals = false; }
+            }
+            match attributes["TEXCOORD_0"].as_u64() {
+                Some(a) => {
+                    for t in gltf.accessor(a)?.0.chunks_exact(2) { uvs.push([t[0] as f32, t[1] as f32]); }
+                }
+                None => { has_uvs = false; }
+            }
+            if normals.len() != positions.len() { normals.resize(positions.len(), [0.0;3]); }
+            if uvs.len() != positions.len() { uvs.resize(positions.len(), [0.0;2]); }
+
+            let order: Vec<u32> = match primitive["indices"].as_u64() {
+                Some(a) => gltf.accessor(a)?.0.iter().map(|&i| i as u32).collect(),
+                None => (0..count as u32).collect(),
+            };
+            if order.iter().any(|&i| i as usize >= count) {
+                return Err("mesh index out of range".to_string());
+            }
+            let material_index = primitive["material"].as_u64().map_or(0, |m| m as u16 + 1);
+            let n = order.len();
+            let mut add_tri = |a: u32, b: u32, c: u32| {
+                let (b, c) = if mirrored { (c, b) } else { (b, c) };
+                indices.extend_from_slice(&[first + a, first + b, first + c]);
+                tri_materials.push(material_index);
+            };
+            match mode {
+                4 => for tri in order.chunks_exact(3) { add_tri(tri[0], tri[1], tri[2]); },
+                5 => for i in 0..n.saturating_sub(2) { //strip, every other tri is wound backwards
+                    if i % 2 == 0 { add_tri(order[i], order[i+1], order[i+2]); }
+                    else { add_tri(order[i+1], order[i], order[i+2]); }
+                },
+                _ => for i in 1..n.saturating_sub(1) { add_tri(order[0], order[i], order[i+1]); }, //fan
+            }
+        }
+    }
+    if tri_materials.iter().any(|&m| m as usize >= materials.len()) {
+        return Err("primitive uses a material that doesn't exist".to_string());
+    }
+    if !has_normals { normals.clear(); }
+    if !has_uvs { uvs.clear(); }
+    Ok(Mesh::new(positions, normals, uvs, indices, materials, textures, tri_materials))
+}
+
+//environment maps: radiance .hdr files, or pngs for low dynamic range ones. either way the
+//result is linear rgb, top row first
+pub fn read_environment(data: &[u8]) -> Result<EnvMap, String> {
+    if data.starts_with(b"#?") {
+        return read_hdr(data);
+    }
+    let texture = read_png(data)?;
+    let pixels = texture.pixels.iter().map(|p| p.to_rgb()).collect();
+    Ok(EnvMap::new(texture.width, texture.height, pixels))
+}
+
+//rgbe: three 8 bit mantissas sharing one exponent. scanlines are either flat, use the old
+//run length encoding where (1,1,1,n) repeats the last pixel, or the newer one where each
+//channel of the scanline is run length encoded on its own
+fn read_hdr(data: &[u8]) -> Result<EnvMap, String> {
+    let truncated = || "truncated hdr file".to_string();
+    let mut at = 0;
+    let mut line = || -> Result<String, String> {
+        let end = data[at..].iter().position(|&b| b == b'\n').ok_or(truncated())?;
+        let text = String::from_utf8_lossy(&data[at..at + end]).into_owned();
+        at += end + 1;
+        Ok(text)
+    };
+    loop {
+        let header = line()?;
+        if header.trim().is_empty() { break; }
+        if let Some(format) = header.strip_prefix("FORMAT=") {
+            if format.trim() != "32-bit_rle_rgbe" {
+                return Err(format!("unsupported hdr format \'{}\'", format.trim()));
+            }
+        }
+    }
+    let resolution = line()?;
+    let words: Vec<&str> = resolution.split_whitespace().collect();
+    let parse = |word: &str| word.parse::<usize>().map_err(|_| format!("invalid hdr resolution \'{}\'", resolution));
+    let (height, width, flipped) = match words[..] {
+        ["-Y", h, "+X", w] => (parse(h)?, parse(w)?, false),
+        ["+Y", h, "+X", w] => (parse(h)?, parse(w)?, true), //bottom row first
+        _ => { return Err(format!("unsupported hdr orientation \'{}\'", resolution)); }
+    };
+    if width == 0 || height == 0 || width.checked_mul(height).is_none_or(|n| n > 1 << 28) {
+        return Err(format!("invalid hdr resolution \'{}\'", resolution));
+    }
+
+    let byte = |at: &mut usize| -> Result<u8, String> {
+        let b = *data.get(*at).ok_or(truncated())?;
+        *at += 1;
+        Ok(b)
+    };
+    let mut rgbe = vec![[0u8;4]; width * height];
+    for y in 0..height {
+        let row = &mut rgbe[y * width..(y + 1) * width];
+        let new_rle = (8..0x8000).contains(&width) && data.get(at..at + 4).is_some_and(|start|
+            start[0] == 2 && start[1] == 2 && ((start[2] as usize) << 8 | start[3] as usize) == width);
+        if new_rle {
+            at += 4;
+            for channel in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = byte(&mut at)? as usize;
+                    let (count, run) = if count > 128 { (count - 128, true) } else { (count, false) };
+                    if count == 0 || x + count > width { return Err("corrupt hdr scanline".to_string()); }
+                    let value = if run { byte(&mut at)? } else { 0 };
+                    for pixel in &mut row[x..x + count] {
+                        pixel[channel] = if run { value } else { byte(&mut at)? };
+                    }
+                    x += count;
+                }
+            }
+        } else {
+            let mut x = 0;
+            let mut shift = 0;
+            while x < width {
+                let pixel = [byte(&mut at)?, byte(&mut at)?, byte(&mut at)?, byte(&mut at)?];
+                if pixel[0] == 1 && pixel[1] == 1 && pixel[2] == 1 {
+                    let count = (pixel[3] as usize) << shift;
+                    if x == 0 || count == 0 || x + count > width { return Err("corrupt hdr scanline".to_string()); }
+                    let last = row[x - 1];
+                    for repeated in &mut row[x..x + count] { *repeated = last; }
+                    x += count;
+                    shift += 8;
+                } else {
+                    row[x] = pixel;
+                    x += 1;
+                    shift = 0;
+                }
+            }
+        }
+    }
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let y = if flipped { height - 1 - y } else { y };
+        for p in &rgbe[y * width..(y + 1) * width] {
+            if p[3] == 0 {
+                pixels.push(Rgb::grey(0.0));
+            } else {
+                let scale = 2f64.powi(p[3] as i32 - 136);
+                pixels.push(Rgb::new((p[0] as f64 + 0.5) * scale, (p[1] as f64 + 0.5) * scale, (p[2] as f64 + 0.5) * scale));
+            }
+        }
+    }
+    Ok(EnvMap::new(width, height, pixels))
+}
+
+//ies lm-63 photometric files. only type c photometry, which nearly every fixture uses.
+//candela are scaled by the multiplier but otherwise left as they are, so lights can use them as
+//measured or relative to the brightest direction
+pub fn read_ies(text: &str) -> Result<IesProfile, String> {
+    let mut lines = text.lines();
+    let tilt = loop {
+        let line = lines.next().ok_or("no TILT line, this isn't an ies file".to_string())?;
+        if let Some(tilt) = line.trim().strip_prefix("TILT=") { break tilt.trim().to_string(); }
+    };
+    let rest: Vec<&str> = lines.flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ',')).filter(|w| !w.is_empty()).collect();
+    let mut numbers = rest.iter().map(|word| word.parse::<f64>().map_err(|_| format!("invalid number \'{}\'", word)));
+    let mut next = || numbers.next().unwrap_or(Err("ies file ends too early".to_string()));
+    match &tilt[..] {
+        "NONE" => {}
+        "INCLUDE" => { //lamp tilt factors, which only matter for fixtures that get tilted. skip them
+            next()?;
+            let count = next()?;
+            if !(0.0..=10000.0).contains(&count) { return Err("invalid number of tilt angles".to_string()); }
+            for _ in 0..count as usize * 2 { next()?; }
+        }
+        _ => { return Err(format!("tilt data in a separate file (\'{}\') isn't supported", tilt)); }
+    }
+    let _lamps = next()?;
+    let _lumens = next()?;
+    let multiplier = next()?;
+    let vertical_count = next()?;
+    let horizontal_count = next()?;
+    let photometric_type = next()?;
+    for _ in 0..7 { next()?; } //units, size of the fixture, ballast factors and watts
+    if photometric_type != 1.0 {
+        return Err("only type c photometry is supported".to_string());
+    }
+    let count_ok = |n: f64| (1.0..=100000.0).contains(&n) && n.fract() == 0.0;
+    if !count_ok(vertical_count) || !count_ok(horizontal_count) || vertical_count * horizontal_count > 1e7 {
+        return Err("invalid number of angles".to_string());
+    }
+    let mut angles = |n: f64| (0..n as usize).map(|_| next()).collect::<Result<Vec<f64>, String>>();
+    let vertical = angles(vertical_count)?;
+    let horizontal = angles(horizontal_count)?;
+    let candela = angles(vertical_count * horizontal_count)?.iter().map(|c| c * multiplier).collect();
+    let ascending = |a: &[f64]| a.windows(2).all(|w| w[0] <= w[1]);
+    if !ascending(&vertical) || !ascending(&horizontal) {
+        return Err("angles must go up in order".to_string());
+    }
+    Ok(IesProfile::new(vertical, horizontal, candela))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material() -> Material { Material::new(Color::new(255, 255, 255, 255), false) }
+    fn ply(data: &[u8]) -> Result<Mesh, String> {
+        read_ply(data, Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0), material())
+    }
+    fn gltf(json: &str) -> Result<Mesh, String> {
+        read_gltf(json.as_bytes(), Path::new("test.gltf"), Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0), material())
+    }
+    //a gltf with one triangle mesh whose positions are accessor 0
+    fn gltf_with(accessor: &str) -> String {
+        format!(r#"{{
+            "nodes": [{{ "mesh": 0 }}],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }} }}] }}],
+            "accessors": [{}]
+        }}"#, accessor)
+    }
+
+    #[test]
+    fn ply_reads_a_triangle() {
+        let mut data = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty float x\n\
+            property float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n".to_vec();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] { data.extend_from_slice(&value.to_le_bytes()); }
+        data.push(3);
+        for index in [0i32, 1, 2] { data.extend_from_slice(&index.to_le_bytes()); }
+        let mesh = ply(&data).unwrap();
+        assert_eq!(mesh.positions.len(), 3);
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn truncated_ply_is_an_error() {
+        let mut data = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty float x\n\
+            property float y\nproperty float z\nend_header\n".to_vec();
+        for value in [0.0f32, 0.0, 0.0, 1.0] { data.extend_from_slice(&value.to_le_bytes()); }
+        assert!(matches!(ply(&data), Err(error) if error == "unexpected end of file"));
+        //counts far beyond the data shouldn't be trusted for anything
+        let data = b"ply\nformat ascii 1.0\nelement vertex 4000000000\nproperty float x\nend_header\n1 2 3\n";
+        assert!(matches!(ply(data), Err(error) if error == "unexpected end of file"));
+    }
+
+    #[test]
+    fn zero_count_accessor_is_an_empty_mesh() {
+        let mesh = gltf(&gltf_with(r#"{ "count": 0, "type": "VEC3", "componentType": 5126 }"#)).unwrap();
+        assert!(mesh.positions.is_empty());
+        assert!(mesh.indices.is_empty());
+    }
+
+    #[test]
+    fn huge_accessor_is_an_error() {
+        //no buffer view means all zeros, which would take exabytes of them
+        let json = gltf_with(r#"{ "count": 4611686018427387904, "type": "VEC3", "componentType": 5126 }"#);
+        assert!(matches!(gltf(&json), Err(error) if error.contains("too many elements")));
+        //and with one, the data has to be there
+        let json = r#"{
+            "nodes": [{ "mesh": 0 }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+            "buffers": [{ "byteLength": 12, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAA" }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 12 }],
+            "accessors": [{ "bufferView": 0, "count": 1000000000000, "type": "VEC3", "componentType": 5126 }]
+        }"#;
+        assert!(matches!(gltf(json), Err(error) if error.contains("out of bounds")));
+    }
+}
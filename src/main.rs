@@ -4,39 +4,63 @@ extern crate png;
 
 use std::env;
 use std::fs;
-use std::fs::File;
 use std::path::Path;
 use std::time::Instant;
-use std::io::BufWriter;
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
 use std::process::exit;
 use std::error;
 use std::collections::HashMap;
+use std::any::Any;
 
 //use crate::png;
 
 
 use std::thread;
-use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::mpsc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::collections::VecDeque;
 
 
 mod scn;
 mod space;
+mod prim;
+mod csg;
+mod sdf;
+mod mesh;
+mod import;
+mod world;
+mod sky;
+mod framebuffer;
+mod exr;
+mod progress;
+mod tile;
 
 pub use space::*;
+pub use prim::*;
+pub use csg::*;
+pub use sdf::*;
+pub use mesh::*;
+pub use world::*;
+pub use sky::*;
+pub use framebuffer::*;
+use progress::Progress;
+use tile::*;
 
 const HELP: &str = r#"
 Usage: raytrs [OPTION]...
 
     -h, --help                      show this message
     -q, --quiet                     quiet mode, only print render time to stdout
-    -s, --scene <filename.json>     set scene file. if no scene is provided, a
+    -s, --scene <filename>          set scene file, in json, yaml or toml depending
+                                    on its extension. if no scene is provided, a
                                     very simple example will be rendered.
-    -o, --output <filename.png>     set output file. defaults to render.png
-    -t, --threads <# of threads>    set number of threads used. should be >= the
-                                    number of logical cores in your system,
-                                    defaults to 32
+    -o, --output <filename.png>     set output file. defaults to render.png. files
+                                    ending in .exr keep the full range of colors,
+                                    along with every aov as a layer.
+    -t, --threads <# of threads>    set number of threads used. defaults to the
+                                    number of logical cores in your system
     -r, --resolution <WIDTHxHEIGHT> set image dimensions. defaults to 256x256
         --samples <# of samples>    if set to a nonzero value, will enable soft
                                     shadows using the set amount of random samples.
@@ -44,13 +68,54 @@ Usage: raytrs [OPTION]...
                                     acceptable results are not possible without
                                     increasing render times by several orders of
                                     magnitude.
+        --mode <shaded|ao>          what to render: the shaded scene (the default), or
+                                    only its ambient occlusion, using the scene's
+                                    ambient radius and samples.
+        --aov <name,name...>        extra passes to save: depth, normal, position,
+                                    albedo, object_id, material_id, shadow, or all.
+                                    unless the output is an exr, each is written
+                                    next to it, eg. render.depth.png
+        --passes <# of passes>      render the image this many times, each with rays
+                                    in different places within the pixels, and
+                                    average them for smoother edges and less noise.
+                                    the image so far is saved every 10 seconds.
+        --time-limit <time>         stop adding passes after this long, eg. 90s, 5m or
+                                    1h. without --passes, passes keep going until then.
+                                    the first pass is always finished.
+        --tile-size <pixels>        width and height of the tiles threads render at a
+                                    time. defaults to 16
+        --tile-order <order>        which tiles are rendered first: spiral, from the
+                                    center out (the default), scanline, or hilbert.
+        --region <x0,y0,x1,y1>      only render this rectangle of the image, from its
+                                    top left corner to its bottom right, in pixels or
+                                    as fractions of the image, each written with a
+                                    decimal point like 0.25,0.0,0.75,0.5.
+                                    the output is just the rectangle, unless
+                                    --composite is set.
+        --composite <image.png>     paste the --region into this full size image
+                                    instead of saving it on its own.
+        --resume                    carry on with a render that was stopped, from the
+                                    checkpoint saved next to the output every 10
+                                    seconds, eg. render.png.checkpoint. the scene
+                                    and settings have to be the same as before.
+        --denoise                   smooth out noise from sampling after rendering,
+                                    keeping edges and textures sharp.
+        --export <filename>         instead of rendering, save the scene with every
+                                    default filled in, as json, yaml or toml
+                                    depending on the extension.
+        --check                     check the scene for errors and exit without
+                                    rendering.
 "#;
 const GET_HELP: &str =
 "\n- see \'raytrs --help\' for more info";
 static mut QUIET: bool = false;
 
-//multiply all brightnesses by this. 30 is pretty good
+//how bright a light given a strength is, per unit of strength. 30 is pretty good
 const EXPOSURE: f64 = 30.0;
+//how far rays leaving a surface start off of it, so the surface doesn't get in their way
+const SURFACE_OFFSET: f64 = 0.0001;
+//most transparent surfaces of one object a shadow ray goes through before giving up
+const MAX_LAYERS: usize = 16;
 
 
 
@@ -73,8 +138,21 @@ fn run() -> Result<(),Box<dyn error::Error>> {
     let mut scene_file: Option<String> = None;
     let mut width: usize = 256;
     let mut height: usize = 256;
-    let mut threads: usize = 32;
+    let mut threads: usize = thread::available_parallelism().map_or(1, |cores| cores.get());
     let mut samples: usize = 0;
+    let mut mode = RenderMode::Shaded;
+    let mut aovs: Vec<Aov> = Vec::new();
+    let mut denoise = false;
+    let mut passes: Option<usize> = Some(1);
+    let mut time_limit: Option<Duration> = None;
+    let mut passes_set = false;
+    let mut resume = false;
+    let mut tile_size: usize = 16;
+    let mut tile_order = TileOrder::Spiral;
+    let mut region_text: Option<String> = None;
+    let mut composite_file: Option<String> = None;
+    let mut export_file: Option<String> = None;
+    let mut check = false;
     
     { //argument parsing
         let opts = [
@@ -106,23 +184,82 @@ fn run() -> Result<(),Box<dyn error::Error>> {
                     .ok_or(invalid_res_error)?
                     .parse().or(Err(invalid_res_error))?;
 
-                if width == 0 { return Err(format!("width cannot be zero")) };
-                if height == 0 { return Err(format!("height cannot be zero")) };
+                if width == 0 { return Err("width cannot be zero".to_string()) };
+                if height == 0 { return Err("height cannot be zero".to_string()) };
                 Ok(())
             })}),
             ("threads", ClOpt::Value{ action: &mut ( |t: String| {
                 threads = t.parse().or(
-                    Err(format!("invalid number of threads"))
+                    Err("invalid number of threads".to_string())
                 )?;
-                if threads == 0 { return Err(format!("number of threads cannot be zero")); }
+                if threads == 0 { return Err("number of threads cannot be zero".to_string()); }
                 Ok(())
             })}),
             ("samples", ClOpt::Value{ action: &mut ( |t: String| {
                 samples = t.parse().or(
-                    Err(format!("invalid number of samples"))
+                    Err("invalid number of samples".to_string())
                 )?;
                 Ok(())
             })}),
+            ("mode", ClOpt::Value{ action: &mut ( |m: String| {
+                mode = match &m[..] {
+                    "shaded" => RenderMode::Shaded,
+                    "ao" => RenderMode::Occlusion,
+                    _ => { return Err(format!("invalid render mode \'{}\' {}", m, GET_HELP)); }
+                };
+                Ok(())
+            })}),
+            ("aov", ClOpt::Value{ action: &mut ( |names: String| {
+                for name in names.split(',') {
+                    match (name, Aov::from_name(name)) {
+                        ("all", _) => { aovs = AOVS.to_vec(); }
+                        (_, Some(aov)) => if !aovs.contains(&aov) { aovs.push(aov); },
+                        _ => { return Err(format!("invalid aov \'{}\' {}", name, GET_HELP)); }
+                    }
+                }
+                Ok(())
+            })}),
+            ("passes", ClOpt::Value{ action: &mut ( |p: String| {
+                let p: usize = p.parse().or(Err("invalid number of passes".to_string()))?;
+                if p == 0 { return Err("number of passes cannot be zero".to_string()); }
+                passes = Some(p);
+                passes_set = true;
+                Ok(())
+            })}),
+            ("time-limit", ClOpt::Value{ action: &mut ( |t: String| {
+                time_limit = Some(parse_duration(&t)?);
+                Ok(())
+            })}),
+            ("region", ClOpt::Value{ action: &mut ( |r: String| {
+                region_text = Some(r);
+                Ok(())
+            })}),
+            ("composite", ClOpt::Value{ action: &mut ( |filename: String| {
+                composite_file = Some(filename);
+                Ok(())
+            })}),
+            ("tile-size", ClOpt::Value{ action: &mut ( |t: String| {
+                tile_size = t.parse().or(Err("invalid tile size".to_string()))?;
+                if tile_size == 0 { return Err("tile size cannot be zero".to_string()); }
+                Ok(())
+            })}),
+            ("tile-order", ClOpt::Value{ action: &mut ( |o: String| {
+                tile_order = TileOrder::from_name(&o).ok_or(format!("invalid tile order '{}' {}", o, GET_HELP))?;
+                Ok(())
+            })}),
+            ("resume", ClOpt::Flag{ action: &mut ( || {
+                resume = true;
+            })}),
+            ("denoise", ClOpt::Flag{ action: &mut ( || {
+                denoise = true;
+            })}),
+            ("check", ClOpt::Flag{ action: &mut ( || {
+                check = true;
+            })}),
+            ("export", ClOpt::Value{ action: &mut ( |filename: String| {
+                export_file = Some(filename);
+                Ok(())
+            })}),
         ];
         let names = [ //translation table for short names
             ("h","help"),
@@ -134,11 +271,34 @@ fn run() -> Result<(),Box<dyn error::Error>> {
         ];
         parse_args(&mut HashMap::from(opts),HashMap::from(names))?;
     }
+    //with only a time limit, keep going until it's up
+    if time_limit.is_some() && !passes_set { passes = None; }
+    //the region can be in fractions of the resolution, so it's only worked out once that's known
+    let region = match &region_text {
+        Some(text) => parse_region(text, width, height)?,
+        None => Tile { x: 0, y: 0, width, height },
+    };
+    let base = match &composite_file {
+        Some(file) => {
+            if !aovs.is_empty() {
+                return Err("aovs can't be composited, only the color".to_string().into());
+            }
+            let data = fs::read(file).map_err(|e| format!("can't read '{}': {}", file, e))?;
+            let base = import::read_png(&data).map_err(|e| format!("can't read '{}': {}", file, e))?;
+            if (base.width, base.height) != (width, height) {
+                return Err(format!(
+                    "'{}' is {}x{}, but the render is {}x{}", file, base.width, base.height, width, height
+                ).into());
+            }
+            Some(base)
+        }
+        None => None,
+    };
   
-    print_loud(format!("loading scene...\n"));
+    print_loud("loading scene...\n".to_string());
 
+    let mut scene_path = Path::new("./");
     let scene = {
-        let mut scene_path = Path::new("./");
         let scene_contents = match &scene_file {
             Some(file) => {
                 scene_path = Path::new(file);
@@ -146,32 +306,173 @@ fn run() -> Result<(),Box<dyn error::Error>> {
             }
             None => { scn::DEFAULT_JSON.to_string() }
         };
-        scn::read_json(&scene_contents,scene_path)?
+        if check {
+            let problems = scn::check_scene(&scene_contents,scene_path);
+            let errors = problems.iter().filter(|problem| problem.is_error).count();
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if errors > 0 {
+                return Err(format!("{} errors and {} warnings", errors, problems.len() - errors).into());
+            }
+            println!("scene is valid, with {} warnings", problems.len());
+            return Ok(());
+        }
+        scn::read_scene(&scene_contents,scene_path)?
     };
 
+    if let Some(file) = export_file {
+        fs::write(&file, scn::write_scene(&scene, Path::new(&file))?)?;
+        print_loud(format!("scene written to \'{}\'\n", &file));
+        return Ok(());
+    }
+
+    //the denoiser needs normals and albedos to tell surfaces apart, even if they aren't saved.
+    //ambient occlusion doesn't have an albedo
+    let mut render_aovs = aovs.clone();
+    if denoise {
+        let guides: &[Aov] = if mode == RenderMode::Shaded { &[Aov::Normal, Aov::Albedo] } else { &[Aov::Normal] };
+        render_aovs.extend(guides.iter().filter(|guide| !aovs.contains(guide)));
+    }
+    let settings = Settings {
+        width, height, threads, samples, mode, aovs: render_aovs,
+        passes, time_limit, tile_size, tile_order,
+        region,
+    };
+    //progressive renders are saved every so often, so there's something to look at early on,
+    //along with a checkpoint so not everything is lost if they're stopped
+    const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+    let output_path = Path::new(&output_file);
+    let checkpoint_file = format!("{}.checkpoint", output_file);
+    let checkpoint_path = Path::new(&checkpoint_file);
+    //the scene is only hashed once there's a checkpoint to read or write, so renders that never
+    //need one don't depend on it being saveable
+    let mut scene_hash = if resume { Some(hash_scene(&scene, scene_path)?) } else { None };
+    let progress = match scene_hash {
+        Some(hash) => {
+            let progress = Progress::read_checkpoint(checkpoint_path, hash, &settings)?;
+            print_loud(format!("resuming from pass {}\n", progress.pass + 1));
+            progress
+        }
+        None => Progress::new(&settings),
+    };
+    let mut last_flush = Instant::now();
+    let mut on_progress = |progress: &Progress| {
+        if last_flush.elapsed() < FLUSH_INTERVAL { return; }
+        last_flush = Instant::now();
+        //any problem writing it will come up again when saving the finished render
+        save(progress.frame(), output_path, denoise, threads, &aovs, base.as_ref().map(|base| (base, region))).ok();
+        let written = scene_hash.map_or_else(|| hash_scene(&scene, scene_path), Ok).and_then(|hash| {
+            scene_hash = Some(hash);
+            progress.write_checkpoint(checkpoint_path, hash)
+        });
+        if let Err(err) = written {
+            eprintln!("[raytrs] {}", err);
+        }
+    };
     let t0 = Instant::now(); //render timer
-    let pixels = scene.render(width,height,threads,samples)?; //render
+    let progress = scene.render(&settings, progress, &mut on_progress)?; //render
     println!("done rendering in {} seconds", t0.elapsed().as_secs_f32());
+    //a render stopped by the time limit can be carried on with later. once every pass is done
+    //the checkpoint isn't needed
+    if settings.passes.is_some_and(|passes| progress.pass >= passes) {
+        if checkpoint_path.exists() { fs::remove_file(checkpoint_path)?; }
+    } else {
+        let hash = scene_hash.map_or_else(|| hash_scene(&scene, scene_path), Ok)?;
+        progress.write_checkpoint(checkpoint_path, hash)?;
+    }
+    if denoise { print_loud("denoising...\n".to_string()); }
 
-    { //write file
-        let file = File::create(&output_file)?;
-        let ref mut w = BufWriter::new(file);
+    for written in save(progress.frame(), output_path, denoise, threads, &aovs, base.as_ref().map(|base| (base, region)))? {
+        print_loud(format!("output written to \'{}\'\n", written));
+    }
+    Ok(())
+}
+
+//writes a render to path, as an exr or pngs depending on its extension, and gives back the
+//names of the files written. only the aovs that were asked for are saved. with a base image,
+//the render goes into it at the region
+fn save(
+    mut frame: Framebuffer, path: &Path, denoise: bool, threads: usize, aovs: &[Aov], base: Option<(&Texture, Tile)>
+) -> Result<Vec<String>, String> {
+    if denoise {
+        frame.denoise(threads);
+    }
+    frame.aovs.retain(|(aov, _)| aovs.contains(aov));
+    if let Some((base, region)) = base {
+        frame = frame.composite(base, region.x, region.y);
+    }
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("exr")) {
+        frame.write_exr(path)?;
+        Ok(vec![path.display().to_string()])
+    } else {
+        frame.write_pngs(path)
+    }
+}
 
-        let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-        encoder.set_color(png::ColorType::Rgb);
-        encoder.set_depth(png::BitDepth::Eight);
-        let mut writer = encoder.write_header()?;
+//a rectangle of a width by height image like 10,20,110,70, from its top left corner to its
+//bottom right, or 0.25,0,0.75,0.5 in fractions of the image
+fn parse_region(text: &str, width: usize, height: usize) -> Result<Tile, String> {
+    let invalid = || format!("invalid region '{}' {}", text, GET_HELP);
+    let values: Vec<&str> = text.split(',').map(|value| value.trim()).collect();
+    if values.len() != 4 { return Err(invalid()); }
+    //values are fractions of the image when they're all written with a decimal point, and pixels
+    //when none are. anything in between is more likely a typo than either
+    let fractions = values.iter().all(|value| value.contains('.'));
+    if !fractions && values.iter().any(|value| value.contains('.')) {
+        return Err(format!("region '{}' mixes pixels and fractions, write fractions like 0.0 and 1.0", text));
+    }
+    let mut corners = [0; 4];
+    for (i, value) in values.iter().enumerate() {
+        let size = if i % 2 == 0 { width } else { height };
+        corners[i] = if fractions {
+            let fraction: f64 = value.parse().or(Err(invalid()))?;
+            if !(0.0..=1.0).contains(&fraction) { return Err(format!("region '{}' is outside of the image", text)); }
+            //the top left rounds down and the bottom right up, so any pixel partly inside is in
+            if i < 2 { (fraction * size as f64).floor() as usize } else { (fraction * size as f64).ceil() as usize }
+        } else {
+            value.parse().or(Err(invalid()))?
+        };
+    }
+    let [x0, y0, x1, y1] = corners;
+    if x1 > width || y1 > height {
+        return Err(format!("region '{}' is outside of the {}x{} image", text, width, height));
+    }
+    if x0 >= x1 || y0 >= y1 {
+        return Err(format!("region '{}' is empty", text));
+    }
+    Ok(Tile { x: x0, y: y0, width: x1 - x0, height: y1 - y0 })
+}
 
-        let mut data = Vec::new();
-        for i in 0..width*height {
-            data.push(pixels[i].r);
-            data.push(pixels[i].g);
-            data.push(pixels[i].b);
-        }
-        writer.write_image_data(&data)?;
-        print_loud(format!("output written to \'{}\'\n", &output_file));
+//what identifies a scene in its checkpoints: the scene with everything filled in, so changes
+//anywhere in it, even in included files, are caught when resuming. meshes and other files it
+//uses can change without it changing, so their sizes and modification times go in too
+fn hash_scene(scene: &Scene, path: &Path) -> Result<u64, String> {
+    let mut hashed = scn::write_scene(scene, path)?.into_bytes();
+    for file in scn::referenced_files(scene) {
+        let metadata = fs::metadata(&file).map_err(|e| format!("can't read '{}': {}", file.display(), e))?;
+        let modified = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+        hashed.extend_from_slice(&metadata.len().to_le_bytes());
+        hashed.extend_from_slice(&modified.map_or(0, |time| time.as_nanos()).to_le_bytes());
     }
-    Ok(())
+    Ok(fnv1a(&hashed))
+}
+
+//a length of time like 90, 90s, 1.5m or 2h. plain numbers are seconds
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let (number, unit) = match text.find(|c: char| c.is_alphabetic()) {
+        Some(i) => text.split_at(i),
+        None => (text, "s"),
+    };
+    let scale = match unit {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => { return Err(format!("invalid unit of time \'{}\' {}", unit, GET_HELP)); }
+    };
+    let number: f64 = number.trim().parse().or(Err(format!("invalid length of time \'{}\' {}", text, GET_HELP)))?;
+    Duration::try_from_secs_f64(number * scale).or(Err(format!("invalid length of time \'{}\' {}", text, GET_HELP)))
 }
 
 fn parse_args<'a>
@@ -216,7 +517,7 @@ fn parse_args<'a>
             return Err(format!("invalid arguments {}", GET_HELP));
         }
     }
-    return Ok(());
+    Ok(())
 }
 
 enum ClOpt<'a> { //types of command line options
@@ -234,231 +535,449 @@ fn print_loud(content: String) {
 }
 
 
+#[derive(Clone,Copy,PartialEq)]
+enum RenderMode {
+    Shaded,
+    Occlusion, //white where nothing's around, darker the more is nearby
+}
+//how to render a scene, as set on the command line
+struct Settings {
+    width: usize,
+    height: usize,
+    threads: usize,
+    samples: usize,
+    mode: RenderMode,
+    aovs: Vec<Aov>,
+    passes: Option<usize>, //None to keep going until the time limit
+    time_limit: Option<Duration>,
+    tile_size: usize,
+    tile_order: TileOrder,
+    region: Tile, //the part of the image that's rendered
+}
+//what a thread renders for one tile, row by row
+struct TilePixels {
+    color: Vec<Rgb>,
+    depth: Vec<f64>,
+    aovs: Vec<Vec<Rgb>>, //one for each of the settings' aovs, in the same order
+}
+impl TilePixels {
+    fn new(size: usize, aovs: &[Aov]) -> TilePixels {
+        TilePixels {
+            color: vec![Rgb::grey(0.0); size],
+            depth: vec![f64::INFINITY; size],
+            aovs: aovs.iter().map(|aov| vec![aov.background(); size]).collect(),
+        }
+    }
+}
+
 pub struct Scene
 {
     objects: Vec<Box<dyn SceneObject + Send + Sync>>,
     lights: Vec<Light>,
     camera: Camera,
     world: World,
+    ambient: Option<Ambient>,
 }
 impl Scene {
     fn new(
-        objects: Vec<Box<dyn SceneObject + Send + Sync>>, lights: Vec<Light>, camera: Camera, world: World
+        objects: Vec<Box<dyn SceneObject + Send + Sync>>, lights: Vec<Light>, camera: Camera, world: World,
+        ambient: Option<Ambient>
     ) -> Scene {
-        Scene { objects: objects, lights: lights, camera: camera, world: world }
+        Scene { objects, lights, camera, world, ambient }
     }
-    fn render(self, width: usize, height: usize, threads: usize, samples: usize) ->
-    Result<Vec<Color>, String> {
-
-        //higher is much better for large scenes
-        const CHUNK_SIZE: usize = 256;
-
-        let num_pixels = width * height;
-        let chunks = num_pixels/CHUNK_SIZE;
-        
-        let scene = Arc::new(self);
-        let dirs = Arc::new(scene.camera.dirs(width, height));
-        let camera_origin = scene.camera.origin;
-        let mut pixels: Vec<Arc<Mutex<[Option<Color>;CHUNK_SIZE]>>> = Vec::with_capacity(chunks);
-        
-        let mut chunk_status: Vec<u8> = Vec::new(); //0=unrendered, 1=in progress, 2=done
-        for _ in 0..chunks {
-            pixels.push(Arc::new(Mutex::new([None;CHUNK_SIZE])));
-            chunk_status.push(0); 
-        }
-        if num_pixels % CHUNK_SIZE != 0 { //chunk at the end for leftover pixels. this won't be totally filled
-            pixels.push(Arc::new(Mutex::new([None;CHUNK_SIZE])));
+    //renders in passes until settings.passes of them are done or the time limit is up, picking
+    //up from progress and calling on_progress whenever a tile is finished. the first pass is
+    //always finished and aims every ray at the corner of its pixel, the rest are spread over the
+    //pixels and averaged with it, which smooths out edges along with any other sampling noise
+    fn render(&self, settings: &Settings, mut progress: Progress, on_progress: &mut dyn FnMut(&Progress)) -> Result<Progress, String> {
+        let deadline = settings.time_limit.map(|limit| Instant::now() + limit);
+        let dirs = self.camera.dirs(settings.width, settings.height);
+        let mut first = true;
+        loop {
+            let finished = settings.passes.is_some_and(|passes| progress.pass >= passes);
+            let out_of_time = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            if progress.pass > 0 && (finished || out_of_time) { break; }
+            let deadline = if progress.pass == 0 { None } else { deadline };
+            self.render_pass(&dirs, settings, &mut progress, deadline, first, on_progress)?;
+            first = false;
+            if progress.tiles_done.contains(&false) { break; } //ran out of time partway through
+            progress.next_pass();
         }
+        Ok(progress)
+    }
+    //one pass over the whole image, or the tiles of it progress doesn't have yet, until the
+    //deadline. first is whether it's the first pass rendered this time around.
+    //the tiles are dealt out to settings.threads workers in turn, so they all go through them in
+    //order, and once a worker runs out it takes tiles from the far end of the next worker's that
+    //has any left. finished tiles are sent back here to be added to progress, so nothing else
+    //ever writes to it
+    fn render_pass(
+        &self, dirs: &[Vec3], settings: &Settings, progress: &mut Progress, deadline: Option<Instant>, first: bool,
+        on_progress: &mut dyn FnMut(&Progress)
+    ) -> Result<(), String> {
+        let (width, height, threads) = (settings.region.width, settings.region.height, settings.threads);
+        let progressive = settings.passes != Some(1);
+        let pass = progress.pass;
+        let tiles = progress.tiles.clone();
 
-        //channel threads use to communicate that they finished their chunk.
-        //main thread will start a new thread occupied with an unrendered chunk
-        //upon recieving the message.
-        let (tx, rx) = mpsc::channel();
-        let mut handles = Vec::with_capacity(threads);
+        let todo: Vec<usize> = (0..tiles.len()).filter(|i| !progress.tiles_done[*i]).collect();
+        let queues: Vec<Mutex<VecDeque<usize>>> = (0..threads).map(|worker| {
+            Mutex::new(todo.iter().copied().skip(worker).step_by(threads).collect())
+        }).collect();
+        //0=unrendered, 1=in progress, 2=done
+        let tile_status: Vec<AtomicU8> = progress.tiles_done.iter().map(|done| AtomicU8::new(if *done { 2 } else { 0 })).collect();
 
-        //threads are started initially by sending the message that all threads
-        //have finished doing nothing, and need to be given work.
-        for _ in 0..threads { tx.send(None).unwrap(); }
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            let mut handles = Vec::with_capacity(threads);
+            for worker in 0..threads {
+                let (queues, tile_status, tiles, tx) = (&queues, &tile_status, &tiles, tx.clone());
+                handles.push(scope.spawn(move || {
+                    loop {
+                        if deadline.is_some_and(|deadline| Instant::now() >= deadline) { break; }
+                        let mut next = queues[worker].lock().unwrap().pop_front();
+                        if next.is_none() {
+                            next = (1..threads).find_map(|i| queues[(worker + i) % threads].lock().unwrap().pop_back());
+                        }
+                        let Some(index) = next else { break; };
+                        tile_status[index].store(1, Ordering::Relaxed);
+                        if tx.send((index, self.render_tile(dirs, settings, pass, tiles[index]))).is_err() { break; }
+                    }
+                }));
+            }
+            drop(tx); //so the loop below ends once every worker has
 
-        loop {
-            let done = rx.recv().unwrap(); //loop waits to recieve message that a thread is done
-            let new_chunk = { //get next chunk to render
-                if done.is_some() {
-                    chunk_status[done.unwrap()] = 2;
+            //the progress indicator is a grid of the tiles, where they are in the image
+            let size = settings.tile_size;
+            let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+            let mut grid = vec![0; columns * rows];
+            for (i, tile) in tiles.iter().enumerate() {
+                grid[tile.y / size * columns + tile.x / size] = i;
+            }
+            let mut drawn = !first; //whether there's a progress indicator to draw over
+            let mut done_tiles = tiles.len() - todo.len();
+            let mut draw = |done_tiles: usize| {
+                if unsafe {QUIET} { return; }
+                if drawn {
+                    println!("\x1b[{}A",rows+2);
                 }
-                let mut new_chunk: Option<usize> = None;
-                for i in 0..chunks {
-                    if chunk_status[i] == 0 {
-                        chunk_status[i] = 1;
-                        new_chunk = Some(i);
-                        break;
+                drawn = true;
+                let pass_number = if progressive { format!(", pass {}", pass + 1) } else { String::new() };
+                println!("rendering on {} threads... {}/{}{}",threads,done_tiles,tiles.len(),pass_number);
+                for row in grid.chunks(columns) {
+                    for i in row {
+                        match tile_status[*i].load(Ordering::Relaxed) {
+                            0 => { print!("░░"); }
+                            1 => { print!("▒▒"); }
+                            _ => { print!("▓▓"); }
+                        }
                     }
+                    println!();
                 }
-                new_chunk
             };
-            if unsafe {!QUIET} { //progress indicator
-                let aspect = (height as f32) / (width as f32);
-                let line_length: usize = ((chunks as f32) / aspect).sqrt() as usize;
-                if new_chunk.is_none() || new_chunk.unwrap() > 0 {
-                    print!("\x1b[{}A\n",chunks/line_length+2);
-                }
-                let mut done_chunks = 0;
-                for i in 0..chunks {
-                    if chunk_status[i] == 2 { done_chunks +=1; }
-                }
-                print!("rendering on {} threads... {}/{}\n",threads,done_chunks,chunks);
-                for i in 0..chunks {
-                    match chunk_status[i] {
-                        0 => { print!("░░"); }
-                        1 => { print!("▒▒"); }
-                        _ => { print!("▓▓"); }
-                    }
-                    if (i+1) % line_length == 0 && i+1 < line_length * (chunks/line_length) {
-                        print!("\n");
-                    }
-                }
-                print!("\n");
+            draw(done_tiles);
+            for (index, pixels) in rx {
+                tile_status[index].store(2, Ordering::Relaxed);
+                progress.add_tile(index, &pixels);
+                on_progress(progress);
+                done_tiles += 1;
+                draw(done_tiles);
             }
 
-            if new_chunk.is_some() { //render the chunk in a new thread, meanwhile restart the loop
-                let chunk_index = new_chunk.unwrap();
-                let dirs = Arc::clone(&dirs);
-                let scene = Arc::clone(&scene);
-                let pixels = Arc::clone(&pixels[chunk_index]);
-                let tx = tx.clone();
-                let handle = thread::spawn(move || { //actual rendering code here:
-                    let mut pixels = pixels.lock().unwrap();
-                    let mut depths = Vec::new();
-                    for i in 0..CHUNK_SIZE { //fill background (really far away) first
-                        pixels[i] = Some(scene.world.color);
-                        depths.push(f64::MAX);
-                    }
-                    for k in 0..scene.objects.len() {
-                        for j in 0..CHUNK_SIZE {
-                            let dir = dirs[(chunk_index * CHUNK_SIZE) + j];
-                            let ray = Ray::new(camera_origin, dir + camera_origin);
-                            let hit = scene.objects[k].raycast(ray);
-                            if hit.is_some() {
-                                let hit = hit.unwrap();
-                                if hit.depth < depths[j] {
-                                    depths[j] = hit.depth;
-                                    if hit.material.reflective {
-                                        pixels[j] = Some(shade_reflective(ray,hit,&scene,3,samples));
-                                    } else {
-                                        pixels[j] = Some(shade_diffuse(hit,&scene.lights,&scene.objects,samples));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    tx.send(Some(chunk_index)).unwrap();
-                });
-                handles.push(handle);
-            }
-            //loop keeps running even after there are no chunks to assign,
-            //but it has to stop when they are all finished rendering
-            else if !(chunk_status.contains(&1)) {
-                break;
+            //a worker that panicked has already printed why
+            let crashed = handles.into_iter().map(|handle| handle.join()).filter(Result::is_err).count();
+            if crashed > 0 {
+                return Err(format!("{} of the render threads crashed", crashed));
             }
-        }
-        for handle in handles {
-            handle.join().unwrap();
-        }
+            Ok(())
+        })
+    }
+    //renders one tile for a pass
+    fn render_tile(&self, dirs: &[Vec3], settings: &Settings, pass: usize, tile: Tile) -> TilePixels {
+        //tiles are within the region, this is where one is in the whole image
+        let image_tile = Tile { x: settings.region.x + tile.x, y: settings.region.y + tile.y, ..tile };
+        let (samples, mode) = (settings.samples, settings.mode);
+        let num_pixels = settings.width * settings.height;
+        let steps = self.camera.pixel_steps(settings.width, settings.height);
+        let camera_origin = self.camera.origin;
+        //aovs are the same every pass, so they only come from the first one
+        let aovs: &[Aov] = if pass == 0 { &settings.aovs } else { &[] };
+        let size = tile.pixels();
+        let mut pixels = TilePixels::new(size, aovs);
 
-        //pixels is currently a vector of arrays of pixels,
-        //merge it into a single vector of pixels:
-        let mut output: Vec<Color> = Vec::new(); 
-        for thread in pixels {
-            for pixel in thread.lock().unwrap().clone() {
-                if pixel.is_some() {
-                    output.push(pixel.unwrap());
-                }
-                else {
-                    output.push(Color::new(0,0,0,255));
+        for i in 0..size {
+            let pixel = image_tile.pixel(i, settings.width);
+            let mut rng = Rng::new((pass * num_pixels + pixel) as u64);
+            let mut dir = dirs[pixel];
+            if pass > 0 { //somewhere random in the pixel
+                dir = dir + steps.0 * rng.next_f64() + steps.1 * rng.next_f64();
+            }
+            let ray = Ray::new(camera_origin, dir + camera_origin);
+            let Some((k, hit)) = closest_hit(ray, &self.objects) else {
+                pixels.color[i] = match mode {
+                    RenderMode::Shaded => self.world.background(dir),
+                    RenderMode::Occlusion => Rgb::grey(1.0),
+                };
+                continue;
+            };
+            pixels.depth[i] = hit.depth;
+            pixels.color[i] = match mode {
+                RenderMode::Shaded => shade(ray,hit,self,4,samples,&mut rng),
+                RenderMode::Occlusion => {
+                    let (radius, rays) = self.ambient.as_ref().map_or((1.0, 16), |ambient| (ambient.radius, ambient.samples));
+                    ambient_occlusion(hit, ray, radius, rays, &self.objects, &mut rng)
                 }
+            };
+            for (a, aov) in aovs.iter().enumerate() {
+                pixels.aovs[a][i] = aov_value(*aov, k, hit, self);
             }
         }
-        Ok(output)
+        pixels
+    }
+}
+//shading for whatever a ray hit. reflections use up recurs_lim
+fn shade
+(ray: Ray, hit: RaycastHit, scene: &Scene, recurs_lim: u8, samples: usize, rng: &mut Rng) -> Rgb {
+    if recurs_lim > 0 && hit.material.reflective {
+        shade_reflective(ray, hit, scene, recurs_lim - 1, samples, rng)
+    } else {
+        shade_diffuse(ray, hit, scene, samples, rng)
     }
 }
 fn shade_diffuse
-(hit: RaycastHit, lights: &Vec<Light>, objects: &Vec<Box<dyn SceneObject + Send + Sync>>, samples: usize) -> Color {
-    let mut lightness = 0.0;
-    for light in lights {
-        match light {
-            Light::Point(point_light) => {
-                //diffuse shading
-                let light_vector = point_light.origin - hit.point;
-                let light_distance = light_vector.magn();
-                let light_dir = light_vector / light_distance;
-                let mut l0 = (light_dir.dot(hit.normal)) * point_light.strength;
-                if l0 < 0.0 { l0 = 0.0 } //clamp
-                let mut new_light = ((l0 * l0) * EXPOSURE) / (light_distance * light_distance);
-                //shadows
-                for i in 0..objects.len() {
-                    if samples == 0 {
-                        let ray = Ray::new( hit.point, point_light.origin ) ;
-                        let hit1 = objects[i].raycast( ray );
-                        if hit1.is_some() {
-                            new_light = 0.0;
-                        }
-                    }
-                    else {
-                        for _ in 0..samples {
-                            let ray = Ray::new( hit.point, point_light.origin + (Vec3::random() * point_light.size)) ;
-                            let hit1 = objects[i].raycast( ray );
-                            if hit1.is_some() {
-                                if hit1.unwrap().depth > 0.01 { //to prevent casting shadow on self
-                                    new_light -= 1.0/(samples as f64);
-                                }
-                            }
-                        }
-                    }
-                }
-                lightness = lightness + new_light;
+(ray: Ray, hit: RaycastHit, scene: &Scene, samples: usize, rng: &mut Rng) -> Rgb {
+    let objects = &scene.objects;
+    let to_viewer = (ray.start - ray.end).unit();
+    //light reaching the surface, which the diffuse color is lit by, and the highlights of the
+    //lights that have a direction
+    let mut light = Rgb::grey(0.0);
+    let mut highlights = Rgb::grey(0.0);
+    let mut add = |to_light: Vec3, irradiance: Rgb| {
+        light = light + irradiance;
+        highlights = highlights + hit.material.highlight(hit.normal, to_light.unit(), to_viewer) * irradiance;
+    };
+    for source in &scene.lights {
+        match source {
+            Light::Point(point) => {
+                add(point.origin - hit.point, point_light(hit, point.origin, &point.power, point.size, objects, samples, rng));
+            }
+            //same as point lights, but only giving off some of their power in each direction
+            Light::Spot(spot) => {
+                let emission = spot.emission(hit.point - spot.origin);
+                if emission <= 0.0 { continue; }
+                add(spot.origin - hit.point, point_light(hit, spot.origin, &spot.power, spot.size, objects, samples, rng) * emission);
             }
+            Light::Ies(ies) => {
+                let emission = ies.emission(hit.point - ies.origin);
+                if emission <= 0.0 { continue; }
+                add(ies.origin - hit.point, point_light(hit, ies.origin, &ies.power, ies.size, objects, samples, rng) * emission);
+            }
+            Light::Sun(sun) => {
+                add(sun.direction, sun_light(hit, sun, objects, samples, rng));
+            }
+        }
+    }
+    if let Some(sun) = scene.world.sun() {
+        add(sun.direction, sun_light(hit, &sun, objects, samples, rng));
+    }
+    if scene.world.lights_scene() {
+        light = light + sky_light(hit, &scene.world, objects, rng);
+    }
+    if let Some(ambient) = &scene.ambient {
+        let open = ambient_occlusion(hit, ray, ambient.radius, ambient.samples, objects, rng);
+        light = light + ambient.color.to_rgb() * ambient.strength * open;
+    }
+    hit.material.color.to_rgb() * light + highlights
+}
+//light from a light at origin reaching a diffuse surface, falling off with the square of the
+//distance and the cosine of the angle it hits at. with samples, shadow rays go to random
+//points on the light's sphere for soft shadows
+fn point_light
+(hit: RaycastHit, origin: Vec3, power: &LightPower, size: f64, objects: &[Box<dyn SceneObject + Send + Sync>], samples: usize, rng: &mut Rng) -> Rgb {
+    let light_vector = origin - hit.point;
+    let distance = light_vector.magn();
+    let cos = (light_vector / distance).dot(hit.normal);
+    if cos <= 0.0 { return Rgb::grey(0.0); }
+    //shadows
+    let rays = samples.max(1);
+    let mut visible = Rgb::grey(0.0);
+    for _ in 0..rays {
+        let target = if samples == 0 { origin } else { origin + rng.direction() * size };
+        let to_target = target - hit.point;
+        visible = visible + shadow(hit, to_target, to_target.magn(), objects);
+    }
+    power.irradiance(distance) * cos * visible / rays as f64
+}
+//light from a sun reaching a diffuse surface. with samples, shadow rays are spread over the
+//sun's disk for soft shadows
+fn sun_light
+(hit: RaycastHit, sun: &SunLight, objects: &[Box<dyn SceneObject + Send + Sync>], samples: usize, rng: &mut Rng) -> Rgb {
+    let dir = sun.direction.unit();
+    let cos = dir.dot(hit.normal);
+    if cos <= 0.0 { return Rgb::grey(0.0); }
+    let rays = samples.max(1);
+    let spread = Matrix3::from_axis(dir);
+    let radius = (sun.angle / 2.0).to_radians().tan();
+    let mut visible = Rgb::grey(0.0);
+    for _ in 0..rays {
+        let mut ray_dir = dir;
+        if samples > 0 && radius > 0.0 {
+            //uniformly over the disk, seen from the surface
+            let (r, phi) = (rng.next_f64().sqrt() * radius, rng.next_f64() * 2.0 * std::f64::consts::PI);
+            ray_dir = spread * Vec3::new(r * phi.cos(), 1.0, r * phi.sin());
+        }
+        visible = visible + shadow(hit, ray_dir, f64::INFINITY, objects);
+    }
+    sun.color.to_rgb() * sun.strength * cos * visible / rays as f64
+}
+//light reaching a diffuse surface from the sky, as a fraction of what would be reflected by
+//a white surface. each sample is weighted by how likely world.sample was to pick it
+fn sky_light
+(hit: RaycastHit, world: &World, objects: &[Box<dyn SceneObject + Send + Sync>], rng: &mut Rng) -> Rgb {
+    let mut light = Rgb::grey(0.0);
+    for _ in 0..world.samples {
+        let (dir, density) = world.sample(hit.normal, rng);
+        let cos = dir.dot(hit.normal);
+        if cos <= 0.0 || density <= 0.0 { continue; }
+        let visible = shadow(hit, dir, f64::INFINITY, objects);
+        if visible == Rgb::grey(0.0) { continue; }
+        light = light + world.radiance(dir) * visible * (cos / (density * std::f64::consts::PI));
+    }
+    light / world.samples as f64
+}
+//what an aov is for a camera ray hitting object k
+fn aov_value(aov: Aov, k: usize, hit: RaycastHit, scene: &Scene) -> Rgb {
+    match aov {
+        Aov::Depth => Rgb::grey(hit.depth),
+        Aov::Normal => Rgb::new(hit.normal.x, hit.normal.y, hit.normal.z),
+        Aov::Position => Rgb::new(hit.point.x, hit.point.y, hit.point.z),
+        Aov::Albedo => hit.material.color.to_rgb(),
+        Aov::ObjectId => Rgb::grey((k + 1) as f64),
+        Aov::MaterialId => Rgb::grey(hit.material.id() as f64),
+        Aov::Shadow => Rgb::grey(shadow_mask(hit, scene)),
+    }
+}
+//how much of the light from the scene's lights is blocked from reaching a surface, counting
+//every light that faces it the same. 0 if none do
+fn shadow_mask(hit: RaycastHit, scene: &Scene) -> f64 {
+    let to = |origin: Vec3| (origin - hit.point, (origin - hit.point).magn());
+    let mut towards = Vec::new(); //direction and distance to each light
+    for source in &scene.lights {
+        match source {
+            Light::Point(point) => towards.push(to(point.origin)),
+            Light::Spot(spot) if spot.emission(hit.point - spot.origin) > 0.0 => towards.push(to(spot.origin)),
+            Light::Ies(ies) if ies.emission(hit.point - ies.origin) > 0.0 => towards.push(to(ies.origin)),
+            Light::Sun(sun) => towards.push((sun.direction, f64::INFINITY)),
             _ => {}
         }
     }
-    let pixel = hit.material.color * lightness;
-    return pixel;
+    if let Some(sun) = scene.world.sun() {
+        towards.push((sun.direction, f64::INFINITY));
+    }
+    let (mut lit, mut facing) = (0.0, 0);
+    for (dir, distance) in towards {
+        if dir.dot(hit.normal) <= 0.0 { continue; }
+        facing += 1;
+        lit += shadow(hit, dir, distance, &scene.objects).luminance();
+    }
+    if facing == 0 { 0.0 } else { 1.0 - lit / facing as f64 }
 }
-fn shade_reflective
-(ray: Ray, hit: RaycastHit, scene: &Scene, recurs_lim: u8, samples: usize) -> Color {
-    let mut pixel = scene.world.color;
-    let mut depth = f64::MAX;
-
-    for i in 0..scene.objects.len() {
-        let new_ray = Ray::new(hit.point, (ray.start - ray.end).unit().reflect(hit.normal) + hit.point);
-        let refl_hit = scene.objects[i].raycast( new_ray );
-        if refl_hit.is_some() && refl_hit.unwrap().depth < depth
-        {
-            let refl_hit = refl_hit.unwrap();
-            depth = refl_hit.depth; 
-            if recurs_lim > 0 && refl_hit.material.reflective {
-                pixel = shade_reflective( new_ray, refl_hit, &scene, recurs_lim - 1, samples);
-            } else {
-                pixel = shade_diffuse( refl_hit, &scene.lights, &scene.objects, samples);
+//how open the surroundings of a surface are, from white if nothing is within radius of it to
+//black if it's completely enclosed. rays are spread over the side of the surface facing the
+//viewer, weighted toward its normal
+fn ambient_occlusion
+(hit: RaycastHit, ray: Ray, radius: f64, samples: usize, objects: &[Box<dyn SceneObject + Send + Sync>], rng: &mut Rng) -> Rgb {
+    if samples == 0 || radius <= 0.0 { return Rgb::grey(1.0); }
+    let normal = if hit.normal.dot(ray.start - ray.end) < 0.0 { hit.normal * -1.0 } else { hit.normal };
+    let mut open = Rgb::grey(0.0);
+    for _ in 0..samples {
+        open = open + shadow(hit, rng.cosine_direction(normal), radius, objects);
+    }
+    open / samples as f64
+}
+//how much of the light coming from dir, distance away from a surface, makes it there: white if
+//nothing is in the way, black if something opaque is, and tinted by every transparent object
+//it goes through. only things between the surface and the light count
+fn shadow
+(hit: RaycastHit, dir: Vec3, distance: f64, objects: &[Box<dyn SceneObject + Send + Sync>]) -> Rgb {
+    let dir = dir.unit();
+    //start off the surface on the light's side of it. floats get less precise farther from
+    //the origin, so the offset grows with it
+    let scale = hit.point.x.abs().max(hit.point.y.abs()).max(hit.point.z.abs()).max(1.0);
+    let offset = SURFACE_OFFSET * scale;
+    let normal = if hit.normal.dot(dir) < 0.0 { hit.normal * -1.0 } else { hit.normal };
+    let start = hit.point + normal * offset;
+    let mut light = Rgb::grey(1.0);
+    for object in objects {
+        //closed objects tint it once for every stretch of the way spent inside them, however many
+        //of their surfaces a raycast would stop at, so every shape of a material casts the same shadow
+        if let Some(intervals) = object.intervals(Ray::new(start, start + dir)) {
+            for interval in intervals.iter().filter(|interval| interval.exit.depth > 0.0 && interval.enter.depth < distance) {
+                if interval.enter.material.transparency <= 0.0 { return Rgb::grey(0.0); }
+                light = light * interval.enter.material.transmittance();
             }
+            continue;
+        }
+        //open surfaces are stepped through one hit at a time
+        let (mut from, mut left) = (start, distance);
+        for _ in 0..MAX_LAYERS {
+            let blocker = match object.raycast(Ray::new(from, from + dir)) {
+                Some(blocker) if blocker.depth < left => blocker,
+                _ => break,
+            };
+            if blocker.material.transparency <= 0.0 { return Rgb::grey(0.0); }
+            light = light * blocker.material.transmittance();
+            let step = blocker.depth + offset;
+            from = from + dir * step;
+            left -= step;
+        }
+    }
+    light
+}
+fn shade_reflective
+(ray: Ray, hit: RaycastHit, scene: &Scene, recurs_lim: u8, samples: usize, rng: &mut Rng) -> Rgb {
+    let reflected = (ray.start - ray.end).unit().reflect(hit.normal);
+    let new_ray = Ray::new(hit.point, reflected + hit.point);
+    match closest_hit(new_ray, &scene.objects) {
+        Some((_, refl_hit)) => shade(new_ray, refl_hit, scene, recurs_lim, samples, rng),
+        None => scene.world.background(reflected),
+    }
+}
+//the closest thing a ray hits and which object it is. only that gets shaded, so farther
+//hits don't use up any random numbers
+fn closest_hit
+(ray: Ray, objects: &[Box<dyn SceneObject + Send + Sync>]) -> Option<(usize, RaycastHit)> {
+    let mut closest: Option<(usize, RaycastHit)> = None;
+    for (k, object) in objects.iter().enumerate() {
+        if let Some(hit) = object.raycast(ray).filter(|hit| closest.is_none_or(|(_, c)| hit.depth < c.depth)) {
+            closest = Some((k, hit));
         }
     }
-    return pixel;
+    closest
 }
 
 impl Camera {
     fn new( origin: Vec3, direction: Vec3, length: f64) -> Camera {
-        Camera { origin: origin, direction: direction, length: length }
+        Camera { origin, direction, length }
     }
-    fn dirs(&self, width: usize, height: usize) -> Vec<Vec3> {
-        print_loud(format!("generating view rays...\n"));
-
-        //first do matrix math to transform the easy-to-understand
-        //camera properties into something that's actually useful:
+    //first do matrix math to transform the easy-to-understand
+    //camera properties into something that's actually useful:
+    fn view_matrix(&self) -> Matrix3 {
         let z_unit = self.direction.unit();
         let x_unit = Vec3::new(0.0,1.0,0.0).cross(z_unit).unit();
         let y_unit = Vec3::new(0.0,1.0,0.0);
+        Matrix3::new(x_unit,y_unit,z_unit)
+    }
+    //how much a view ray changes going one pixel right, and one pixel down
+    fn pixel_steps(&self, width: usize, height: usize) -> (Vec3, Vec3) {
+        let aspect = (width as f64) / (height as f64);
+        let view_matrix = self.view_matrix();
+        (view_matrix * Vec3::new(aspect / width as f64, 0.0, 0.0), view_matrix * Vec3::new(0.0, -1.0 / height as f64, 0.0))
+    }
+    fn dirs(&self, width: usize, height: usize) -> Vec<Vec3> {
+        print_loud("generating view rays...\n".to_string());
 
-        let view_matrix = Matrix3::new(x_unit,y_unit,z_unit);
+        let view_matrix = self.view_matrix();
 
         let aspect = (width as f64) / (height as f64);
         let half = aspect/2.0;
@@ -486,13 +1005,14 @@ impl Camera {
             }
             dir.y -= dy;
         }
-        return dirs;
+        dirs
 
     }
 }
 
 
 impl SceneObject for Tri {
+    fn as_any(&self) -> &dyn Any { self }
     fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
         //Moller-Trumbore algorithm:
         const EPSILON: f64 = 0.000001;
@@ -512,7 +1032,7 @@ impl SceneObject for Tri {
         let s = ray.start - self.verts.0;
         let u = f * (s.dot(h));
         
-        if u < 0.0 || u > 1.0
+        if !(0.0..=1.0).contains(&u)
         { return None; }
 
         
@@ -529,15 +1049,26 @@ impl SceneObject for Tri {
 
             let normal = (self.vx_normals.1 * u) + (self.vx_normals.2 * v) + (self.vx_normals.0 * (1.0 - u - v)); 
 
-            return Some(RaycastHit::new(point, normal, t, self.material ));
+            Some(RaycastHit::new(point, normal, t, (u, v), self.material ))
         }
         else
-        { return None; }
+        { None}
     }
 }
 
-impl SceneObject for Sphere {
-    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+impl Sphere {
+    fn hit_at(&self, ray: Ray, t: f64) -> RaycastHit {
+        let delta = (ray.end - ray.start).unit();
+        let point = ray.start + (delta * t);
+        let normal = (point - self.center)/self.radius;
+        let uv = (
+            (normal.z.atan2(normal.x) + std::f64::consts::PI) / (2.0 * std::f64::consts::PI),
+            normal.y.clamp(-1.0, 1.0).acos() / std::f64::consts::PI,
+        );
+        RaycastHit::new(point, normal, t, uv, self.material)
+    }
+    //both solutions of the quadratic, nearest first
+    fn roots(&self, ray: Ray) -> Option<(f64,f64)> {
         let delta = (ray.end - ray.start).unit();
        
         let a = delta.dot(delta);
@@ -548,36 +1079,102 @@ impl SceneObject for Sphere {
                 self.radius * self.radius;
 
         let dsc = b * b - (4.0 * a * c);
-
-        let mut hit: Option<RaycastHit> = None;
-
-        if dsc >= 0.0 {
-            let t = (- b - dsc.sqrt()) / (2.0 * a);
-            let point = ray.start + (delta * t);
-            if (point - ray.start).dot(delta) > 0.0 { //check that sphere is not behind ray
-                let normal = (point - self.center)/self.radius;
-                hit = Some(RaycastHit::new(point, normal, t, self.material));
-            }
-        }
-        return hit;
+        if dsc < 0.0 { return None; }
+        Some(((- b - dsc.sqrt()) / (2.0 * a), (- b + dsc.sqrt()) / (2.0 * a)))
     }
 }
-impl SceneObject for Floor {
+impl SceneObject for Sphere {
+    fn as_any(&self) -> &dyn Any { self }
     fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
-        let dir = (ray.end - ray.start).unit();
-        if dir.y >= 0.0 {
-            return None;
-        }
-        else {
-            let t = (self.y - ray.start.y) / dir.y;
-            let point = dir * t + ray.start;
+        let mut hit: Option<RaycastHit> = None;
 
-            return Some(RaycastHit::new(point, Vec3::new(0.0,1.0,0.0), t, self.material));
+        if let Some((t, _)) = self.roots(ray) {
+            if t > 0.0 { //check that sphere is not behind ray
+                hit = Some(self.hit_at(ray, t));
+            }
         }
+        hit
+    }
+    fn intervals(&self, ray: Ray) -> Option<Vec<Interval>> {
+        let spans = match self.roots(ray) {
+            Some((t0, t1)) => vec![Interval::new(self.hit_at(ray, t0), self.hit_at(ray, t1))],
+            None => Vec::new(),
+        };
+        Some(spans)
     }
 }
-
-trait SceneObject {
+pub trait SceneObject {
     //check intersection of self and a given ray
     fn raycast(&self, ray: Ray) -> Option<RaycastHit>;
+
+    //every stretch of the ray (including behind its start) that's inside of self, in order.
+    //only closed objects have an inside, everything else returns None
+    fn intervals(&self, _ray: Ray) -> Option<Vec<Interval>> { None }
+
+    //lets the scene writer find out what kind of object it's looking at
+    fn as_any(&self) -> &dyn Any;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glass() -> Material {
+        Material::new(Color::new(255, 128, 0, 255), false).with_transparency(0.5)
+    }
+    //light from straight above a surface at the origin, past one object
+    fn shadow_of(object: Box<dyn SceneObject + Send + Sync>) -> Rgb {
+        let hit = RaycastHit::new(Vec3::new(0.0,0.0,0.0), Vec3::new(0.0,1.0,0.0), 1.0, (0.0,0.0), glass());
+        shadow(hit, Vec3::new(0.0,1.0,0.0), 10.0, &[object])
+    }
+
+    #[test]
+    fn transparent_shapes_tint_shadows_alike() {
+        let center = Vec3::new(0.0,2.0,0.0);
+        let sphere = shadow_of(Box::new(Sphere::new(center, 0.5, glass())));
+        let cuboid = shadow_of(Box::new(Cuboid::new(center, Vec3::new(1.0,1.0,1.0), Matrix3::identity(), glass())));
+        let cylinder = shadow_of(Box::new(Cylinder::new(Vec3::new(0.0,1.5,0.0), Vec3::new(0.0,1.0,0.0), 0.5, 1.0, glass())));
+        assert_eq!(sphere, glass().transmittance());
+        assert_eq!(cuboid, sphere);
+        assert_eq!(cylinder, sphere);
+    }
+
+    #[test]
+    fn only_things_before_the_light_cast_shadows() {
+        let opaque = Material::new(Color::new(255, 255, 255, 255), false);
+        assert_eq!(shadow_of(Box::new(Sphere::new(Vec3::new(0.0,2.0,0.0), 0.5, opaque))), Rgb::grey(0.0));
+        assert_eq!(shadow_of(Box::new(Sphere::new(Vec3::new(0.0,12.0,0.0), 0.5, opaque))), Rgb::grey(1.0));
+        //open surfaces are tinted by each one they're in the way with
+        let plane = Plane::new(Frame::new(Vec3::new(0.0,2.0,0.0), Matrix3::identity()), None, true, glass());
+        assert_eq!(shadow_of(Box::new(plane)), glass().transmittance());
+    }
+
+    #[test]
+    fn regions_in_pixels_or_fractions() {
+        assert_eq!(parse_region("10,10,50,50", 64, 64), Ok(Tile { x: 10, y: 10, width: 40, height: 40 }));
+        assert_eq!(parse_region("0.25,0.0,0.75,0.5", 64, 32), Ok(Tile { x: 16, y: 0, width: 32, height: 16 }));
+        //pixels partly inside a fraction are in
+        assert_eq!(parse_region("0.1,0.1,0.2,0.2", 64, 64), Ok(Tile { x: 6, y: 6, width: 7, height: 7 }));
+        //without a decimal point 0,0,1,1 is the top left pixel, not the whole image
+        assert_eq!(parse_region("0,0,1,1", 64, 64), Ok(Tile { x: 0, y: 0, width: 1, height: 1 }));
+    }
+
+    #[test]
+    fn bad_regions_are_rejected() {
+        for region in ["10,10,50", "10,10,50,50,60", "a,b,c,d", "", "-1,0,10,10"] {
+            assert!(parse_region(region, 64, 64).is_err(), "{}", region);
+        }
+        //some fractions and some pixels
+        for region in ["0.25,0,0.75,0.5", "10,10,0.5,50"] {
+            assert!(parse_region(region, 64, 64).is_err_and(|error| error.contains("mixes")), "{}", region);
+        }
+        //empty
+        for region in ["10,10,10,50", "50,10,10,50", "0.5,0.0,0.5,1.0"] {
+            assert!(parse_region(region, 64, 64).is_err_and(|error| error.contains("empty")), "{}", region);
+        }
+        //out of bounds
+        for region in ["0,0,65,64", "0,0,64,100", "0.0,0.0,1.5,1.0"] {
+            assert!(parse_region(region, 64, 64).is_err_and(|error| error.contains("outside")), "{}", region);
+        }
+    }
 }
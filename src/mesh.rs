@@ -0,0 +1,270 @@
+//indexed triangle meshes. vertices are shared between faces and stored as f32 to keep
+//big models small, and a bounding volume hierarchy keeps intersection fast even with
+//millions of triangles.
+
+use std::path::PathBuf;
+
+use crate::*;
+
+const EPSILON: f64 = 0.000001;
+//most triangles a leaf of the bvh can hold before it gets split
+const LEAF_SIZE: usize = 4;
+
+pub struct Mesh {
+    pub positions: Vec<[f32;3]>,
+    pub normals: Vec<[f32;3]>, //one per vertex, or empty to use flat face normals
+    pub uvs: Vec<[f32;2]>, //one per vertex, or empty to use barycentric coordinates
+    pub indices: Vec<u32>, //three vertices per triangle
+    pub materials: Vec<Material>,
+    pub textures: Vec<Option<Texture>>, //color textures for materials, same order
+    pub tri_materials: Vec<u16>, //index into materials for each triangle
+    pub source: Option<MeshSource>, //None for meshes built in code
+    bvh: Vec<BvhNode>,
+}
+
+//the file a mesh was loaded from and how, so scenes can be saved without copying the mesh.
+//materials[0] is still whatever color/reflective the mesh was given
+#[derive(Clone)]
+pub struct MeshSource {
+    pub format: MeshFormat,
+    pub path: PathBuf,
+    pub offset: Vec3,
+    pub scale: Vec3,
+    pub use_mtl: bool, //only for obj
+}
+#[derive(Clone,Copy)]
+pub enum MeshFormat { Obj, Ply, Stl, Gltf }
+
+//image mapped onto a surface by its uvs, tinting the material color.
+//v goes from the top row down, and uvs outside of 0..1 wrap around
+#[derive(Clone)]
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+impl Texture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Texture
+    { Texture { width, height, pixels } }
+    //bilinear filtered color at uv
+    pub fn sample(&self, uv: (f64,f64)) -> Color {
+        let x = (uv.0 - uv.0.floor()) * self.width as f64 - 0.5;
+        let y = (uv.1 - uv.1.floor()) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let texel = |x: f64, y: f64| {
+            let x = (x as i64).rem_euclid(self.width as i64) as usize;
+            let y = (y as i64).rem_euclid(self.height as i64) as usize;
+            self.pixels[y * self.width + x]
+        };
+        let corners = [
+            (texel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+            (texel(x0 + 1.0, y0), fx * (1.0 - fy)),
+            (texel(x0, y0 + 1.0), (1.0 - fx) * fy),
+            (texel(x0 + 1.0, y0 + 1.0), fx * fy),
+        ];
+        let mut sum = [0.0;4];
+        for (c, w) in corners {
+            sum[0] += c.r as f64 * w;
+            sum[1] += c.g as f64 * w;
+            sum[2] += c.b as f64 * w;
+            sum[3] += c.a as f64 * w;
+        }
+        Color::new(sum[0].round() as u8, sum[1].round() as u8, sum[2].round() as u8, sum[3].round() as u8)
+    }
+}
+
+//leaves hold count triangles starting at first. inner nodes have a count of 0,
+//their left child is the node right after them and first is the right child
+#[derive(Clone,Copy)]
+struct BvhNode {
+    min: [f32;3],
+    max: [f32;3],
+    first: u32,
+    count: u32,
+}
+
+impl Mesh {
+    pub fn new(
+        positions: Vec<[f32;3]>, normals: Vec<[f32;3]>, uvs: Vec<[f32;2]>,
+        indices: Vec<u32>, materials: Vec<Material>, textures: Vec<Option<Texture>>, tri_materials: Vec<u16>
+    ) -> Mesh {
+        let mut mesh = Mesh {
+            positions, normals, uvs, indices,
+            materials, textures, tri_materials,
+            source: None, bvh: Vec::new()
+        };
+        mesh.build_bvh();
+        mesh
+    }
+    pub fn tri_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+    fn vertex(&self, tri: usize, corner: usize) -> Vec3 {
+        let p = self.positions[self.indices[tri * 3 + corner] as usize];
+        Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64)
+    }
+
+    fn build_bvh(&mut self) {
+        let tris = self.tri_count();
+        //an empty root would look like an inner node, so meshes without tris get no bvh at all
+        if tris == 0 { return; }
+        let mut centroids = Vec::with_capacity(tris);
+        for tri in 0..tris {
+            centroids.push((self.vertex(tri,0) + self.vertex(tri,1) + self.vertex(tri,2)) / 3.0);
+        }
+        let mut order: Vec<u32> = (0..tris as u32).collect();
+        let mut nodes = Vec::with_capacity(tris / LEAF_SIZE * 2 + 1);
+        self.build_node(&mut nodes, &mut order, 0, &centroids);
+
+        //put the triangles in the order the leaves expect them in
+        let mut indices = Vec::with_capacity(self.indices.len());
+        let mut tri_materials = Vec::with_capacity(tris);
+        for &tri in &order {
+            let tri = tri as usize;
+            indices.extend_from_slice(&self.indices[tri * 3..tri * 3 + 3]);
+            tri_materials.push(self.tri_materials[tri]);
+        }
+        self.indices = indices;
+        self.tri_materials = tri_materials;
+        self.bvh = nodes;
+    }
+    fn build_node(&self, nodes: &mut Vec<BvhNode>, tris: &mut [u32], first: usize, centroids: &[Vec3]) -> usize {
+        let mut min = [f32::MAX;3];
+        let mut max = [f32::MIN;3];
+        let mut centroid_min = Vec3::new(f64::MAX,f64::MAX,f64::MAX);
+        let mut centroid_max = Vec3::new(f64::MIN,f64::MIN,f64::MIN);
+        for &tri in tris.iter() {
+            for corner in 0..3 {
+                let p = self.positions[self.indices[tri as usize * 3 + corner] as usize];
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(p[axis]);
+                    max[axis] = max[axis].max(p[axis]);
+                }
+            }
+            let c = centroids[tri as usize];
+            centroid_min = Vec3::new(centroid_min.x.min(c.x), centroid_min.y.min(c.y), centroid_min.z.min(c.z));
+            centroid_max = Vec3::new(centroid_max.x.max(c.x), centroid_max.y.max(c.y), centroid_max.z.max(c.z));
+        }
+        let index = nodes.len();
+        nodes.push(BvhNode { min, max, first: first as u32, count: tris.len() as u32 });
+        if tris.len() <= LEAF_SIZE { return index; }
+
+        //split in half along the axis the centroids are most spread out on
+        let extent = centroid_max - centroid_min;
+        let axis = if extent.x > extent.y && extent.x > extent.z { 0 } else if extent.y > extent.z { 1 } else { 2 };
+        let key = |tri: &u32| {
+            let c = centroids[*tri as usize];
+            match axis { 0 => c.x, 1 => c.y, _ => c.z }
+        };
+        let mid = tris.len() / 2;
+        tris.select_nth_unstable_by(mid, |a, b| key(a).total_cmp(&key(b)));
+
+        let (left, right) = tris.split_at_mut(mid);
+        self.build_node(nodes, left, first, centroids);
+        let right_index = self.build_node(nodes, right, first + mid, centroids);
+        nodes[index].first = right_index as u32;
+        nodes[index].count = 0;
+        index
+    }
+
+    //moller-trumbore, same as for lone tris. returns distance and barycentric coordinates
+    fn intersect_tri(&self, tri: usize, start: Vec3, dir: Vec3) -> Option<(f64,f64,f64)> {
+        let v0 = self.vertex(tri,0);
+        let edge0 = self.vertex(tri,1) - v0;
+        let edge1 = self.vertex(tri,2) - v0;
+        let h = dir.cross(edge1);
+        let a = edge0.dot(h);
+        if a < EPSILON { return None; }
+
+        let f = 1.0/a;
+        let s = start - v0;
+        let u = f * s.dot(h);
+        if !(0.0..=1.0).contains(&u) { return None; }
+
+        let q = s.cross(edge0);
+        let v = f * dir.dot(q);
+        if v < 0.0 || u + v > 1.0 { return None; }
+
+        let t = f * edge1.dot(q);
+        if t > EPSILON { Some((t, u, v)) } else { None }
+    }
+    fn interpolate(&self, values: &[[f32;3]], tri: usize, u: f64, v: f64) -> Vec3 {
+        let get = |corner: usize| {
+            let x = values[self.indices[tri * 3 + corner] as usize];
+            Vec3::new(x[0] as f64, x[1] as f64, x[2] as f64)
+        };
+        get(0) * (1.0 - u - v) + get(1) * u + get(2) * v
+    }
+}
+
+//whether the ray passes through the node's box before max_t
+fn hit_box(node: &BvhNode, start: Vec3, inv_dir: Vec3, max_t: f64) -> bool {
+    let mut t_near: f64 = 0.0;
+    let mut t_far = max_t;
+    for (axis, (s, inv)) in [(start.x, inv_dir.x), (start.y, inv_dir.y), (start.z, inv_dir.z)].iter().enumerate() {
+        let mut t0 = (node.min[axis] as f64 - s) * inv;
+        let mut t1 = (node.max[axis] as f64 - s) * inv;
+        if t0 > t1 { std::mem::swap(&mut t0, &mut t1); }
+        t_near = t_near.max(t0);
+        t_far = t_far.min(t1);
+        if t_near > t_far { return false; }
+    }
+    true
+}
+
+impl SceneObject for Mesh {
+    fn as_any(&self) -> &dyn Any { self }
+    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+        if self.bvh.is_empty() { return None; }
+        let dir = (ray.end - ray.start).unit();
+        let inv_dir = Vec3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+
+        let mut closest: Option<(usize, f64, f64, f64)> = None;
+        let mut max_t = f64::MAX;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(index) = stack.pop() {
+            let node = &self.bvh[index];
+            if !hit_box(node, ray.start, inv_dir, max_t) { continue; }
+            if node.count == 0 {
+                stack.push(node.first as usize);
+                stack.push(index + 1);
+                continue;
+            }
+            for tri in node.first as usize..(node.first + node.count) as usize {
+                if let Some((t, u, v)) = self.intersect_tri(tri, ray.start, dir) {
+                    if t < max_t {
+                        max_t = t;
+                        closest = Some((tri, t, u, v));
+                    }
+                }
+            }
+        }
+
+        let (tri, t, u, v) = closest?;
+        let point = ray.start + dir * t;
+        let normal = if self.normals.is_empty() {
+            (self.vertex(tri,1) - self.vertex(tri,0)).cross(self.vertex(tri,2) - self.vertex(tri,0)).unit()
+        } else {
+            self.interpolate(&self.normals, tri, u, v).unit()
+        };
+        let uv = if self.uvs.is_empty() {
+            (u, v)
+        } else {
+            let get = |corner: usize| self.uvs[self.indices[tri * 3 + corner] as usize];
+            let (a, b, c) = (get(0), get(1), get(2));
+            let w = 1.0 - u - v;
+            (
+                a[0] as f64 * w + b[0] as f64 * u + c[0] as f64 * v,
+                a[1] as f64 * w + b[1] as f64 * u + c[1] as f64 * v,
+            )
+        };
+        let material_index = self.tri_materials[tri] as usize;
+        let mut material = self.materials[material_index];
+        if let Some(Some(texture)) = self.textures.get(material_index) {
+            material.color = material.color * texture.sample(uv);
+        }
+        Some(RaycastHit::new(point, normal, t, uv, material))
+    }
+}
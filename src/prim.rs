@@ -0,0 +1,471 @@
+//analytic primitives beyond tris and spheres.
+//most of these are intersected in a local frame where the shape sits at the
+//origin and is aligned with the y axis, then the hit is moved back to world space.
+
+use std::f64::consts::PI;
+
+use crate::*;
+
+const EPSILON: f64 = 0.000001;
+
+//a candidate intersection, in the local space of whatever shape produced it
+#[derive(Clone,Copy)]
+struct Crossing {
+    t: f64,
+    normal: Vec3,
+    uv: (f64,f64),
+}
+impl Crossing {
+    fn new(t: f64, normal: Vec3, uv: (f64,f64)) -> Crossing
+    { Crossing { t, normal, uv } }
+}
+
+//position and orientation of a shape. basis columns are the local axes in world space,
+//and since they're orthonormal, distances along a ray are the same in both spaces.
+#[derive(Clone,Copy)]
+pub struct Frame {
+    pub origin: Vec3,
+    pub basis: Matrix3,
+}
+impl Frame {
+    pub fn new(origin: Vec3, basis: Matrix3) -> Frame
+    { Frame { origin, basis } }
+    fn to_local(self, ray: Ray) -> (Vec3, Vec3) {
+        let inverse = self.basis.transpose();
+        let dir = (ray.end - ray.start).unit();
+        (inverse * (ray.start - self.origin), inverse * dir)
+    }
+    fn hit(self, ray: Ray, crossing: &Crossing, material: Material) -> RaycastHit {
+        let dir = (ray.end - ray.start).unit();
+        let point = ray.start + dir * crossing.t;
+        let normal = (self.basis * crossing.normal).unit();
+        RaycastHit::new(point, normal, crossing.t, crossing.uv, material)
+    }
+    //turn the closest crossing in front of the ray into a proper hit
+    fn nearest(self, ray: Ray, crossings: &[Crossing], material: Material) -> Option<RaycastHit> {
+        let mut nearest: Option<&Crossing> = None;
+        for crossing in crossings {
+            if crossing.t > EPSILON && nearest.is_none_or(|n| crossing.t < n.t) {
+                nearest = Some(crossing);
+            }
+        }
+        Some(self.hit(ray, nearest?, material))
+    }
+    //pair up the crossings of a closed shape into the stretches the ray spends inside it.
+    //an odd one out can only come from grazing an edge, so it's dropped
+    fn intervals(self, ray: Ray, mut crossings: Vec<Crossing>, material: Material) -> Vec<Interval> {
+        crossings.sort_by(|a, b| a.t.total_cmp(&b.t));
+        crossings.chunks_exact(2).map(|pair| {
+            Interval::new(self.hit(ray, &pair[0], material), self.hit(ray, &pair[1], material))
+        }).collect()
+    }
+}
+
+fn component(v: Vec3, i: usize) -> f64 {
+    match i { 0 => v.x, 1 => v.y, _ => v.z }
+}
+fn unit_axis(i: usize) -> Vec3 {
+    match i {
+        0 => Vec3::new(1.0,0.0,0.0),
+        1 => Vec3::new(0.0,1.0,0.0),
+        _ => Vec3::new(0.0,0.0,1.0),
+    }
+}
+//angle around the local y axis, mapped to 0..1
+fn around_y(p: Vec3) -> f64 {
+    (p.z.atan2(p.x) + PI) / (2.0 * PI)
+}
+//crossing with a flat circle at height y in local space, facing up or down
+fn cap(o: Vec3, d: Vec3, y: f64, radius: f64, facing: f64) -> Option<Crossing> {
+    if d.y.abs() < EPSILON { return None; }
+    let t = (y - o.y) / d.y;
+    let p = o + d * t;
+    let r = (p.x * p.x + p.z * p.z).sqrt();
+    if r > radius { return None; }
+    Some(Crossing::new(t, Vec3::new(0.0,facing,0.0), (around_y(p), r / radius)))
+}
+
+
+//plane through a point, either infinite or a width x height rectangle centered on the point.
+//rectangle edges follow the frame's local x and z axes. when one sided, only the side the
+//normal faces is visible, otherwise the normal is flipped to face whoever is looking.
+pub struct Plane {
+    pub frame: Frame,
+    pub extents: Option<(f64,f64)>,
+    pub two_sided: bool,
+    pub material: Material,
+}
+impl Plane {
+    pub fn new(frame: Frame, extents: Option<(f64,f64)>, two_sided: bool, material: Material) -> Plane
+    { Plane { frame, extents, two_sided, material } }
+}
+impl SceneObject for Plane {
+    fn as_any(&self) -> &dyn Any { self }
+    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+        let (o, d) = self.frame.to_local(ray);
+        let facing = facing(d, self.two_sided)?;
+        let t = -o.y / d.y;
+        let p = o + d * t;
+        let uv = match self.extents {
+            Some((width, height)) => {
+                if p.x.abs() > width / 2.0 || p.z.abs() > height / 2.0 { return None; }
+                (p.x / width + 0.5, p.z / height + 0.5)
+            }
+            None => (p.x, p.z),
+        };
+        self.frame.nearest(ray, &[Crossing::new(t, Vec3::new(0.0,facing,0.0), uv)], self.material)
+    }
+}
+
+pub struct Disk {
+    pub frame: Frame,
+    pub radius: f64,
+    pub two_sided: bool,
+    pub material: Material,
+}
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f64, two_sided: bool, material: Material) -> Disk {
+        Disk {
+            frame: Frame::new(center, Matrix3::from_axis(normal)),
+            radius, two_sided, material
+        }
+    }
+}
+impl SceneObject for Disk {
+    fn as_any(&self) -> &dyn Any { self }
+    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+        let (o, d) = self.frame.to_local(ray);
+        let crossing = cap(o, d, 0.0, self.radius, facing(d, self.two_sided)?)?;
+        self.frame.nearest(ray, &[crossing], self.material)
+    }
+}
+//which way the local y normal of a flat shape should point for a ray going in direction d,
+//or None if the ray can't see it
+fn facing(d: Vec3, two_sided: bool) -> Option<f64> {
+    if d.y < 0.0 {
+        Some(1.0)
+    } else if two_sided && d.y > 0.0 {
+        Some(-1.0)
+    } else {
+        None
+    }
+}
+
+//box, axis aligned unless rotated. "Box" is already taken by std
+pub struct Cuboid {
+    pub frame: Frame,
+    pub half_size: Vec3,
+    pub material: Material,
+}
+impl Cuboid {
+    pub fn new(center: Vec3, size: Vec3, rotation: Matrix3, material: Material) -> Cuboid {
+        Cuboid { frame: Frame::new(center, rotation), half_size: size / 2.0, material }
+    }
+    fn crossings(&self, o: Vec3, d: Vec3) -> Vec<Crossing> {
+        //slab method, keeping track of which face the ray enters and leaves through
+        let mut near = (f64::MIN, 0);
+        let mut far = (f64::MAX, 0);
+        for i in 0..3 {
+            let (oi, di, hi) = (component(o,i), component(d,i), component(self.half_size,i));
+            if di.abs() < EPSILON {
+                if oi.abs() > hi { return Vec::new(); }
+                continue;
+            }
+            let (mut t0, mut t1) = ((-hi - oi) / di, (hi - oi) / di);
+            if t0 > t1 { std::mem::swap(&mut t0, &mut t1); }
+            if t0 > near.0 { near = (t0, i); }
+            if t1 < far.0 { far = (t1, i); }
+        }
+        if near.0 > far.0 { return Vec::new(); }
+
+        let face = |(t, i): (f64, usize), sign: f64| {
+            let p = o + d * t;
+            let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+            let u = (component(p,j) / component(self.half_size,j) + 1.0) / 2.0;
+            let v = (component(p,k) / component(self.half_size,k) + 1.0) / 2.0;
+            Crossing::new(t, unit_axis(i) * sign, (u, v))
+        };
+        vec![
+            face(near, -component(d, near.1).signum()),
+            face(far, component(d, far.1).signum()),
+        ]
+    }
+}
+impl SceneObject for Cuboid {
+    fn as_any(&self) -> &dyn Any { self }
+    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+        let (o, d) = self.frame.to_local(ray);
+        self.frame.nearest(ray, &self.crossings(o, d), self.material)
+    }
+    fn intervals(&self, ray: Ray) -> Option<Vec<Interval>> {
+        let (o, d) = self.frame.to_local(ray);
+        Some(self.frame.intervals(ray, self.crossings(o, d), self.material))
+    }
+}
+
+//capped cylinder standing on its base, extending along its axis
+pub struct Cylinder {
+    pub frame: Frame,
+    pub radius: f64,
+    pub height: f64,
+    pub material: Material,
+}
+impl Cylinder {
+    pub fn new(base: Vec3, axis: Vec3, radius: f64, height: f64, material: Material) -> Cylinder {
+        Cylinder {
+            frame: Frame::new(base, Matrix3::from_axis(axis)),
+            radius, height, material
+        }
+    }
+    fn crossings(&self, o: Vec3, d: Vec3) -> Vec<Crossing> {
+        let mut crossings = Vec::new();
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.z * d.z);
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        let dsc = b * b - 4.0 * a * c;
+        if a > EPSILON && dsc >= 0.0 {
+            for t in [(-b - dsc.sqrt()) / (2.0 * a), (-b + dsc.sqrt()) / (2.0 * a)] {
+                let p = o + d * t;
+                if p.y >= 0.0 && p.y <= self.height {
+                    let normal = Vec3::new(p.x, 0.0, p.z) / self.radius;
+                    crossings.push(Crossing::new(t, normal, (around_y(p), p.y / self.height)));
+                }
+            }
+        }
+        crossings.extend(cap(o, d, 0.0, self.radius, -1.0));
+        crossings.extend(cap(o, d, self.height, self.radius, 1.0));
+        crossings
+    }
+}
+impl SceneObject for Cylinder {
+    fn as_any(&self) -> &dyn Any { self }
+    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+        let (o, d) = self.frame.to_local(ray);
+        self.frame.nearest(ray, &self.crossings(o, d), self.material)
+    }
+    fn intervals(&self, ray: Ray) -> Option<Vec<Interval>> {
+        let (o, d) = self.frame.to_local(ray);
+        Some(self.frame.intervals(ray, self.crossings(o, d), self.material))
+    }
+}
+
+//capped cone with its base on the base point and its tip at height along the axis
+pub struct Cone {
+    pub frame: Frame,
+    pub radius: f64,
+    pub height: f64,
+    pub material: Material,
+}
+impl Cone {
+    pub fn new(base: Vec3, axis: Vec3, radius: f64, height: f64, material: Material) -> Cone {
+        Cone {
+            frame: Frame::new(base, Matrix3::from_axis(axis)),
+            radius, height, material
+        }
+    }
+    fn crossings(&self, o: Vec3, d: Vec3) -> Vec<Crossing> {
+        //x^2 + z^2 = (k * (h - y))^2, where k is the slope of the side
+        let h = self.height;
+        let k2 = (self.radius / h) * (self.radius / h);
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.z * d.z + k2 * (h - o.y) * d.y);
+        let c = o.x * o.x + o.z * o.z - k2 * (h - o.y) * (h - o.y);
+
+        let mut roots = Vec::new();
+        if a.abs() < EPSILON { //ray parallel to the side, only one root
+            if b.abs() > EPSILON { roots.push(-c / b); }
+        } else {
+            let dsc = b * b - 4.0 * a * c;
+            if dsc >= 0.0 {
+                roots.push((-b - dsc.sqrt()) / (2.0 * a));
+                roots.push((-b + dsc.sqrt()) / (2.0 * a));
+            }
+        }
+        let mut crossings = Vec::new();
+        for t in roots {
+            let p = o + d * t;
+            if p.y >= 0.0 && p.y <= h {
+                let normal = Vec3::new(p.x, k2 * (h - p.y), p.z).unit();
+                crossings.push(Crossing::new(t, normal, (around_y(p), p.y / h)));
+            }
+        }
+        crossings.extend(cap(o, d, 0.0, self.radius, -1.0));
+        crossings
+    }
+}
+impl SceneObject for Cone {
+    fn as_any(&self) -> &dyn Any { self }
+    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+        let (o, d) = self.frame.to_local(ray);
+        self.frame.nearest(ray, &self.crossings(o, d), self.material)
+    }
+    fn intervals(&self, ray: Ray) -> Option<Vec<Interval>> {
+        let (o, d) = self.frame.to_local(ray);
+        Some(self.frame.intervals(ray, self.crossings(o, d), self.material))
+    }
+}
+
+//ring around the axis. major radius is from the center to the middle of the tube,
+//minor radius is the thickness of the tube
+pub struct Torus {
+    pub frame: Frame,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Material,
+}
+impl Torus {
+    pub fn new(center: Vec3, axis: Vec3, major_radius: f64, minor_radius: f64, material: Material) -> Torus {
+        Torus {
+            frame: Frame::new(center, Matrix3::from_axis(axis)),
+            major_radius, minor_radius, material
+        }
+    }
+    fn crossings(&self, o: Vec3, d: Vec3) -> Vec<Crossing> {
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+
+        //only search for roots inside the bounding sphere, starting from where the ray enters it.
+        //moving the origin there also keeps the quartic coefficients small
+        let bound = big_r + small_r;
+        let b = o.dot(d);
+        let dsc = b * b - (o.dot(o) - bound * bound);
+        if dsc < 0.0 { return Vec::new(); }
+        let t0 = -b - dsc.sqrt();
+        let t1 = -b + dsc.sqrt();
+        let o = o + d * t0;
+
+        //(|p|^2 + R^2 - r^2)^2 = 4R^2(x^2 + z^2), expanded in terms of t
+        let e = o.dot(o) - big_r * big_r - small_r * small_r;
+        let f = o.dot(d);
+        let four_r2 = 4.0 * big_r * big_r;
+        let coeffs = [
+            e * e - four_r2 * (small_r * small_r - o.y * o.y),
+            4.0 * f * e + 2.0 * four_r2 * o.y * d.y,
+            2.0 * e + 4.0 * f * f + four_r2 * d.y * d.y,
+            4.0 * f,
+            1.0,
+        ];
+        let mut crossings = Vec::new();
+        for t in poly_roots(&coeffs, 0.0, t1 - t0) {
+            let p = o + d * t;
+            let ring = (p.x * p.x + p.z * p.z).sqrt();
+            let center = Vec3::new(p.x, 0.0, p.z) * (big_r / ring); //middle of the tube
+            let tube = p - center;
+            let v = (tube.y.atan2(ring - big_r) + PI) / (2.0 * PI);
+            crossings.push(Crossing::new(t + t0, tube.unit(), (around_y(p), v)));
+        }
+        crossings
+    }
+}
+impl SceneObject for Torus {
+    fn as_any(&self) -> &dyn Any { self }
+    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+        let (o, d) = self.frame.to_local(ray);
+        self.frame.nearest(ray, &self.crossings(o, d), self.material)
+    }
+    fn intervals(&self, ray: Ray) -> Option<Vec<Interval>> {
+        let (o, d) = self.frame.to_local(ray);
+        Some(self.frame.intervals(ray, self.crossings(o, d), self.material))
+    }
+}
+
+fn poly_eval(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+//real roots in [lo, hi] of the polynomial with the given coefficients (constant term first).
+//roots of the derivative split the interval into pieces where the polynomial is monotonic,
+//each of which can have at most one root, found by bisection.
+fn poly_roots(coeffs: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    if coeffs.len() < 2 { return Vec::new(); }
+    if coeffs.len() == 2 {
+        if coeffs[1] == 0.0 { return Vec::new(); }
+        let root = -coeffs[0] / coeffs[1];
+        return if root >= lo && root <= hi { vec![root] } else { Vec::new() };
+    }
+    let derivative: Vec<f64> = coeffs.iter().enumerate().skip(1).map(|(i, c)| c * i as f64).collect();
+    let mut bounds = vec![lo];
+    bounds.extend(poly_roots(&derivative, lo, hi));
+    bounds.push(hi);
+
+    let mut roots = Vec::new();
+    for piece in bounds.windows(2) {
+        let (mut a, mut b) = (piece[0], piece[1]);
+        let mut fa = poly_eval(coeffs, a);
+        let fb = poly_eval(coeffs, b);
+        if fa * fb > 0.0 { continue; }
+        for _ in 0..64 {
+            let mid = (a + b) / 2.0;
+            let fm = poly_eval(coeffs, mid);
+            if (fm < 0.0) == (fa < 0.0) { a = mid; fa = fm; } else { b = mid; }
+            if b - a < EPSILON * EPSILON { break; }
+        }
+        let root = (a + b) / 2.0;
+        if roots.last().is_none_or(|last: &f64| root - last > EPSILON) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool { (a - b).abs() < 1e-6 }
+    fn torus() -> Torus {
+        Torus::new(Vec3::new(0.0,0.0,0.0), Vec3::new(0.0,1.0,0.0), 2.0, 0.5, Material::new(Color::new(255,255,255,255), false))
+    }
+    fn ray(start: Vec3, dir: Vec3) -> Ray { Ray::new(start, start + dir) }
+
+    #[test]
+    fn quartic_roots() {
+        //(x - 1)(x - 2)(x - 3)(x - 4)
+        let coeffs = [24.0, -50.0, 35.0, -10.0, 1.0];
+        let roots = poly_roots(&coeffs, 0.0, 5.0);
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) { assert!(close(*root, expected), "{}", root); }
+        //only the ones in range
+        let roots = poly_roots(&coeffs, 1.5, 3.5);
+        assert!(roots.len() == 2 && close(roots[0], 2.0) && close(roots[1], 3.0));
+        //x^4 + 1 has none at all
+        assert!(poly_roots(&[1.0, 0.0, 0.0, 0.0, 1.0], -10.0, 10.0).is_empty());
+    }
+
+    #[test]
+    fn torus_hole_misses() {
+        assert!(torus().raycast(ray(Vec3::new(0.0,10.0,0.0), Vec3::new(0.0,-1.0,0.0))).is_none());
+        //and so does a ray through the hole at an angle that stays inside the tube's inner edge
+        assert!(torus().raycast(ray(Vec3::new(-1.0,10.0,0.0), Vec3::new(0.1,-1.0,0.0))).is_none());
+    }
+
+    #[test]
+    fn torus_across_the_middle() {
+        //straight through the middle of the ring, the tube is crossed at major - minor and
+        //major + minor from the center on both sides
+        let torus = torus();
+        let across = ray(Vec3::new(-10.0,0.0,0.0), Vec3::new(1.0,0.0,0.0));
+        let hit = torus.raycast(across).unwrap();
+        assert!(close(hit.depth, 10.0 - 2.5));
+        assert!(close(hit.normal.x, -1.0));
+        let intervals = torus.intervals(across).unwrap();
+        assert_eq!(intervals.len(), 2);
+        let depths: Vec<f64> = intervals.iter().flat_map(|i| [i.enter.depth, i.exit.depth]).collect();
+        for (depth, expected) in depths.iter().zip([7.5, 8.5, 11.5, 12.5]) { assert!(close(*depth, expected), "{:?}", depths); }
+        //normals point out of the tube, toward the hole on its inner side
+        assert!(close(intervals[0].exit.normal.x, 1.0));
+        assert!(close(intervals[1].enter.normal.x, -1.0));
+        //from inside the tube, the first hit is on the way out
+        let hit = torus.raycast(ray(Vec3::new(2.0,0.0,0.0), Vec3::new(1.0,0.0,0.0))).unwrap();
+        assert!(close(hit.depth, 0.5) && close(hit.normal.x, 1.0));
+    }
+
+    #[test]
+    fn torus_tangent() {
+        //skimming over the top of the tube touches it right above the middle of it
+        let hit = torus().raycast(ray(Vec3::new(-10.0,0.5,0.0), Vec3::new(1.0,0.0,0.0)));
+        let hit = hit.unwrap();
+        assert!((hit.depth - 8.0).abs() < 1e-3, "{}", hit.depth);
+        assert!(hit.normal.y > 0.999);
+        //and just above it, nothing
+        assert!(torus().raycast(ray(Vec3::new(-10.0,0.501,0.0), Vec3::new(1.0,0.0,0.0))).is_none());
+    }
+}
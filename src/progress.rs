@@ -0,0 +1,280 @@
+//how far along a render is, and checkpoint files that save it so a render that gets stopped can
+//be picked back up later with --resume. random numbers come from the pass and the pixel, so
+//the pass a render was on is all there is to its random state.
+
+use std::fs;
+use std::path::Path;
+use std::convert::{TryFrom, TryInto};
+
+use crate::*;
+
+const MAGIC: &[u8] = b"raytrs checkpoint\n";
+const VERSION: u32 = 3;
+
+//every pixel's colors added up over the passes that reached it, and what the first pass found
+//there for the depth and aovs
+pub struct Progress {
+    pub pass: usize, //the one being rendered
+    pub tiles: Vec<Tile>,
+    pub tiles_done: Vec<bool>, //of the current pass
+    width: usize,
+    height: usize,
+    region: Tile, //what's being rendered of the width by height image, and all that's kept
+    samples: usize,
+    mode: RenderMode,
+    aovs: Vec<Aov>,
+    tile_size: usize,
+    tile_order: TileOrder,
+    sum: Vec<Rgb>,
+    count: Vec<u32>,
+    depth: Vec<f64>,
+    layers: Vec<Vec<Rgb>>, //one for each aov
+}
+impl Progress {
+    pub fn new(settings: &Settings) -> Progress {
+        let region = settings.region;
+        let pixels = region.pixels();
+        let tiles = tiles(region.width, region.height, settings.tile_size, settings.tile_order);
+        Progress {
+            pass: 0,
+            tiles_done: vec![false; tiles.len()],
+            tiles,
+            width: settings.width,
+            height: settings.height,
+            region,
+            samples: settings.samples,
+            mode: settings.mode,
+            aovs: settings.aovs.clone(),
+            tile_size: settings.tile_size,
+            tile_order: settings.tile_order,
+            sum: vec![Rgb::grey(0.0); pixels],
+            count: vec![0; pixels],
+            depth: vec![f64::INFINITY; pixels],
+            layers: settings.aovs.iter().map(|aov| vec![aov.background(); pixels]).collect(),
+        }
+    }
+    pub fn add_tile(&mut self, index: usize, pixels: &TilePixels) {
+        let tile = self.tiles[index];
+        for i in 0..tile.pixels() {
+            let p = tile.pixel(i, self.region.width);
+            self.sum[p] = self.sum[p] + pixels.color[i];
+            self.count[p] += 1;
+            if self.pass == 0 {
+                self.depth[p] = pixels.depth[i];
+                for (layer, values) in self.layers.iter_mut().zip(&pixels.aovs) {
+                    layer[p] = values[i];
+                }
+            }
+        }
+        self.tiles_done[index] = true;
+    }
+    pub fn next_pass(&mut self) {
+        self.pass += 1;
+        for done in &mut self.tiles_done { *done = false; }
+    }
+    //the average of everything so far. pixels nothing has reached yet are black
+    pub fn frame(&self) -> Framebuffer {
+        let color = self.sum.iter().zip(&self.count).map(|(sum, count)| {
+            if *count == 0 { Rgb::grey(0.0) } else { *sum / *count as f64 }
+        }).collect();
+        let aovs = self.aovs.iter().copied().zip(self.layers.iter().cloned()).collect();
+        Framebuffer::new(self.region.width, self.region.height, color, self.depth.clone(), aovs)
+    }
+
+    //scene_hash is whatever identifies the scene, checked when resuming
+    pub fn write_checkpoint(&self, path: &Path, scene_hash: u64) -> Result<(), String> {
+        let mut out = Vec::from(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&scene_hash.to_le_bytes());
+        let order = TILE_ORDERS.iter().position(|order| *order == self.tile_order).unwrap_or(0);
+        let region = self.region;
+        for n in [
+            self.width, self.height, region.x, region.y, region.width, region.height, self.samples, mode_code(self.mode),
+            self.tile_size, order, self.aovs.len()
+        ] {
+            out.extend_from_slice(&(n as u64).to_le_bytes());
+        }
+        for aov in &self.aovs {
+            out.extend_from_slice(&(AOVS.iter().position(|a| a == aov).unwrap_or(0) as u64).to_le_bytes());
+        }
+        out.extend_from_slice(&(self.pass as u64).to_le_bytes());
+        out.extend(self.tiles_done.iter().map(|done| *done as u8));
+        let rgb = |out: &mut Vec<u8>, v: &Rgb| for c in [v.r, v.g, v.b] { out.extend_from_slice(&c.to_le_bytes()); };
+        for i in 0..self.sum.len() {
+            rgb(&mut out, &self.sum[i]);
+            out.extend_from_slice(&self.count[i].to_le_bytes());
+            out.extend_from_slice(&self.depth[i].to_le_bytes());
+            for layer in &self.layers { rgb(&mut out, &layer[i]); }
+        }
+        //written next to it first, so being stopped partway through doesn't ruin the last one
+        let temporary = path.with_extension("tmp");
+        let error = |e: std::io::Error| format!("can't write checkpoint \'{}\': {}", path.display(), e);
+        fs::write(&temporary, out).map_err(error)?;
+        fs::rename(&temporary, path).map_err(error)
+    }
+    //a checkpoint, as long as it was made for the same scene and settings
+    pub fn read_checkpoint(path: &Path, scene_hash: u64, settings: &Settings) -> Result<Progress, String> {
+        let data = fs::read(path).map_err(|e| format!("can't read checkpoint \'{}\': {}", path.display(), e))?;
+        let mut reader = Reader { data: &data, at: 0 };
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(format!("\'{}\' isn't a checkpoint", path.display()));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("checkpoint is from a different version of raytrs ({}, this one reads {})", version, VERSION));
+        }
+        if reader.u64()? != scene_hash {
+            return Err("the scene has changed since the checkpoint was made".to_string());
+        }
+        let (width, height) = (reader.usize()?, reader.usize()?);
+        if (width, height) != (settings.width, settings.height) {
+            return Err(format!("checkpoint is for a {}x{} render, not {}x{}", width, height, settings.width, settings.height));
+        }
+        let region = Tile { x: reader.usize()?, y: reader.usize()?, width: reader.usize()?, height: reader.usize()? };
+        if region != settings.region {
+            return Err(format!(
+                "checkpoint is for the region {},{},{},{}", region.x, region.y, region.x + region.width, region.y + region.height
+            ));
+        }
+        let (samples, mode) = (reader.usize()?, reader.usize()?);
+        if samples != settings.samples {
+            return Err(format!("checkpoint was rendered with {} samples, not {}", samples, settings.samples));
+        }
+        if mode != mode_code(settings.mode) {
+            return Err("checkpoint was rendered in a different --mode".to_string());
+        }
+        let (tile_size, order) = (reader.usize()?, reader.usize()?);
+        if tile_size != settings.tile_size {
+            return Err(format!("checkpoint was rendered with {} pixel tiles, not {}", tile_size, settings.tile_size));
+        }
+        if TILE_ORDERS.get(order) != Some(&settings.tile_order) {
+            return Err("checkpoint was rendered in a different --tile-order".to_string());
+        }
+        let aov_count = reader.usize()?;
+        let mut aovs = Vec::new();
+        for _ in 0..aov_count {
+            aovs.push(*AOVS.get(reader.usize()?).ok_or("checkpoint has an unknown aov".to_string())?);
+        }
+        if aovs != settings.aovs {
+            let names: Vec<&str> = aovs.iter().map(|aov| aov.name()).collect();
+            return Err(format!("checkpoint was rendered with different aovs ({})", if names.is_empty() { "none".to_string() } else { names.join(",") }));
+        }
+
+        let mut progress = Progress::new(settings);
+        progress.pass = reader.usize()?;
+        for done in &mut progress.tiles_done { *done = reader.bytes(1)?[0] != 0; }
+        for i in 0..progress.sum.len() {
+            progress.sum[i] = reader.rgb()?;
+            progress.count[i] = reader.u32()?;
+            progress.depth[i] = reader.f64()?;
+            for layer in &mut progress.layers { layer[i] = reader.rgb()?; }
+        }
+        if reader.at != data.len() {
+            return Err("checkpoint has extra data at the end".to_string());
+        }
+        Ok(progress)
+    }
+}
+
+fn mode_code(mode: RenderMode) -> usize {
+    match mode { RenderMode::Shaded => 0, RenderMode::Occlusion => 1 }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.at..self.at + count).ok_or("checkpoint is cut short".to_string())?;
+        self.at += count;
+        Ok(bytes)
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+    fn usize(&mut self) -> Result<usize, String> {
+        usize::try_from(self.u64()?).or(Err("checkpoint has a number too big for this computer".to_string()))
+    }
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+    fn rgb(&mut self) -> Result<Rgb, String> {
+        Ok(Rgb::new(self.f64()?, self.f64()?, self.f64()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            width: 8, height: 6, threads: 1, samples: 2, mode: RenderMode::Shaded, aovs: vec![Aov::Depth, Aov::Normal],
+            passes: Some(4), time_limit: None, tile_size: 2, tile_order: TileOrder::Hilbert,
+            region: Tile { x: 1, y: 1, width: 5, height: 3 },
+        }
+    }
+    //a checkpoint partway through the second pass, with different values everywhere
+    fn progress(settings: &Settings) -> Progress {
+        let mut progress = Progress::new(settings);
+        for pass in 0..2 {
+            for index in 0..progress.tiles.len() - pass * 2 {
+                let size = progress.tiles[index].pixels();
+                let mut pixels = TilePixels::new(size, &settings.aovs);
+                for i in 0..size {
+                    let value = (pass * 100 + index * 10 + i) as f64;
+                    pixels.color[i] = Rgb::new(value, value + 0.25, value + 0.5);
+                    pixels.depth[i] = value * 2.0;
+                    for aov in &mut pixels.aovs { aov[i] = Rgb::grey(value * 3.0); }
+                }
+                progress.add_tile(index, &pixels);
+            }
+            if pass == 0 { progress.next_pass(); }
+        }
+        progress
+    }
+    fn path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("raytrs-{}-{}.checkpoint", name, std::process::id()))
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let settings = settings();
+        let progress = progress(&settings);
+        let path = path("round-trip");
+        progress.write_checkpoint(&path, 1234).unwrap();
+        let read = Progress::read_checkpoint(&path, 1234, &settings);
+        fs::remove_file(&path).unwrap();
+        let read = read.unwrap();
+        assert_eq!(read.pass, 1);
+        assert_eq!(read.tiles, progress.tiles);
+        assert_eq!(read.tiles_done, progress.tiles_done);
+        assert!(read.tiles_done.contains(&true) && read.tiles_done.contains(&false));
+        assert_eq!(read.sum, progress.sum);
+        assert_eq!(read.count, progress.count);
+        assert_eq!(read.depth, progress.depth);
+        assert_eq!(read.layers, progress.layers);
+    }
+
+    #[test]
+    fn checkpoint_for_something_else_is_rejected() {
+        let original = settings();
+        let path = path("rejected");
+        progress(&original).write_checkpoint(&path, 1234).unwrap();
+        let changed = [
+            Settings { samples: 4, ..settings() },
+            Settings { tile_order: TileOrder::Spiral, ..settings() },
+            Settings { aovs: vec![Aov::Depth], ..settings() },
+            Settings { region: Tile { x: 0, y: 1, width: 5, height: 3 }, ..settings() },
+        ];
+        let results: Vec<bool> = changed.iter().map(|changed| Progress::read_checkpoint(&path, 1234, changed).is_err()).collect();
+        let other_scene = Progress::read_checkpoint(&path, 4321, &original).is_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(results, vec![true; changed.len()]);
+        assert!(other_scene);
+    }
+}
@@ -1,186 +1,1033 @@
 extern crate serde_json;
+extern crate serde_yaml;
+extern crate toml;
 extern crate serde;
 use std::fs;
-use std::path::Path;
-use std::error;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
 
-use self::serde::Deserialize;
+use self::serde::{Deserialize, Serialize};
+use self::serde::de::DeserializeOwned;
+use self::serde_yaml::with::singleton_map_recursive;
 
 use crate::*;
 
 
-pub fn read_json(contents: &str, path: &Path) -> Result<Scene, String> {
-    let scn: IpScene = serde_json::from_str(contents).map_err(
-        move |error| { format!("error parsing json: {}", error) }
-    )?;
-    let scene = scn.to_scene(path)?;
+mod validate;
+pub use self::validate::Problem;
+
+//warnings are printed, errors stop the scene from loading
+pub fn read_scene(contents: &str, path: &Path) -> Result<Scene, String> {
+    let files = load_files(contents, path).map_err(|problem| format!("{}", problem))?;
+    let mut errors = String::new();
+    for problem in validate::check(&files) {
+        if problem.is_error {
+            errors += &format!("\n    {}", problem);
+        } else {
+            print_loud(format!("{}\n", problem));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(format!("invalid scene:{}", errors));
+    }
+    let scene = into_scene(files)?;
     Ok(scene)
 }
 
+//every problem with a scene, including any with loading the meshes it uses, without rendering it
+pub fn check_scene(contents: &str, path: &Path) -> Vec<Problem> {
+    let files = match load_files(contents, path) {
+        Ok(files) => files,
+        Err(problem) => { return vec![problem]; }
+    };
+    let mut problems = validate::check(&files);
+    if !problems.iter().any(|problem| problem.is_error) {
+        if let Err(error) = into_scene(files) {
+            problems.push(Problem::error("", error));
+        }
+    }
+    problems
+}
+
+//a scene file along with everything needed to report problems in it
+struct SceneFile {
+    scene: IpScene,
+    text: String,
+    path: PathBuf, //mesh filenames and includes are relative to this
+    name: Option<String>, //None for the main scene file
+    format: Format,
+}
+
+//the scene file and every file it includes, directly or not. included files come before
+//the file including them, so the main scene file is always last
+fn load_files(contents: &str, path: &Path) -> Result<Vec<SceneFile>, Problem> {
+    let mut files = Vec::new();
+    load_file(contents.to_string(), path, None, &mut Vec::new(), &mut files)?;
+    Ok(files)
+}
+fn load_file(
+    text: String, path: &Path, name: Option<String>, chain: &mut Vec<PathBuf>, files: &mut Vec<SceneFile>
+) -> Result<(), Problem> {
+    let format = Format::from_path(path);
+    let scene = parse_scene(&text, format).map_err(|mut problem| { problem.file = name.clone(); problem })?;
+    //chain is every file that led to this one, any of them showing up again would never end
+    chain.push(fs::canonicalize(path).unwrap_or(path.to_path_buf()));
+    for (i, include) in scene.include.iter().enumerate() {
+        let include_path = path.parent().unwrap().join(include);
+        let problem = |message: String| {
+            let at = format!("include[{}]", i);
+            let mut problem = Problem::error(&at, message);
+            problem.location = format.locate(&text, &at);
+            problem.file = name.clone();
+            problem
+        };
+        let canonical = fs::canonicalize(&include_path).unwrap_or(include_path.clone());
+        if chain.contains(&canonical) {
+            return Err(problem(format!("\'{}\' ends up including itself", include)));
+        }
+        //files included more than once, through different files, are only loaded the first time
+        //so their objects and lights aren't doubled up
+        if files.iter().any(|file| fs::canonicalize(&file.path).unwrap_or(file.path.clone()) == canonical) {
+            continue;
+        }
+        let contents = fs::read_to_string(&include_path).map_err(
+            |error| problem(format!("error reading \'{}\': {}", include, error))
+        )?;
+        let include_name = include_path.to_string_lossy().into_owned();
+        load_file(contents, &include_path, Some(include_name), chain, files)?;
+    }
+    chain.pop();
+    files.push(SceneFile { scene, text, path: path.to_path_buf(), name, format });
+    Ok(())
+}
+
+//scene files can be json, yaml or toml, picked by their extension. anything else is json
+#[derive(Clone,Copy,PartialEq)]
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+impl Format {
+    fn from_path(path: &Path) -> Format {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("yaml") | Some("yml") => Format::Yaml,
+            Some("toml") => Format::Toml,
+            _ => Format::Json,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self { Format::Json => "json", Format::Yaml => "yaml", Format::Toml => "toml" }
+    }
+    //where a path like objects[3].sphere.radius is in the text
+    fn locate(self, text: &str, path: &str) -> Option<(usize,usize)> {
+        match self {
+            Format::Json => validate::locate(text, path),
+            Format::Yaml => validate::walk(serde_yaml::Deserializer::from_str(text), path).err()
+                .and_then(|error| error.location()).map(|l| (l.line(), l.column())),
+            Format::Toml => validate::walk(toml::Deserializer::new(text), path).err()
+                .and_then(|error| error.span()).map(|span| line_column(text, span.start)),
+        }
+    }
+    fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, Problem> {
+        //each library reports where the error is differently, but they all end up as a line and column
+        let (message, location) = match self {
+            Format::Json => match serde_json::from_str(text) {
+                Ok(value) => { return Ok(value); }
+                Err(error) => {
+                    let location = if error.line() > 0 { Some((error.line(), error.column())) } else { None };
+                    (format!("{}", error), location)
+                }
+            },
+            //yaml would otherwise want enums written as tags (!sphere), instead of { sphere: } like
+            //in the other formats
+            Format::Yaml => match singleton_map_recursive::deserialize(serde_yaml::Deserializer::from_str(text)) {
+                Ok(value) => { return Ok(value); }
+                Err(error) => {
+                    let location = error.location().map(|l| (l.line(), l.column()));
+                    (format!("{}", error), location)
+                }
+            },
+            Format::Toml => match toml::from_str(text) {
+                Ok(value) => { return Ok(value); }
+                Err(error) => {
+                    let location = error.span().map(|span| line_column(text, span.start));
+                    (error.message().to_string(), location)
+                }
+            },
+        };
+        //problems keep the location separately, so take it off the end of the message
+        let message = match message.rfind(" at line ") {
+            Some(end) if location.is_some() => message[..end].to_string(),
+            _ => message,
+        };
+        let mut problem = Problem::error("", format!("invalid {}: {}", self.name(), message));
+        problem.location = location;
+        Err(problem)
+    }
+    fn write<T: Serialize>(self, value: &T) -> Result<String, String> {
+        let text = match self {
+            Format::Json => serde_json::to_string_pretty(value).map_err(|error| format!("{}", error)),
+            Format::Yaml => {
+                let mut text = Vec::new();
+                singleton_map_recursive::serialize(value, &mut serde_yaml::Serializer::new(&mut text))
+                    .map(|_| String::from_utf8_lossy(&text).into_owned())
+                    .map_err(|error| format!("{}", error))
+            }
+            Format::Toml => toml::to_string_pretty(value).map_err(|error| format!("{}", error)),
+        };
+        text.map_err(|error| format!("error writing {}: {}", self.name(), error))
+    }
+}
+
+//line and column of a byte offset into text
+fn line_column(text: &str, offset: usize) -> (usize,usize) {
+    let before = &text[..offset.min(text.len())];
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (before.matches('\n').count() + 1, column)
+}
+
+fn parse_scene(text: &str, format: Format) -> Result<IpScene, Problem> {
+    //look at the version on its own first, since a newer file probably won't parse at all
+    #[derive(Deserialize)]
+    struct Version {
+        #[serde(default = "current_version")]
+        version: u32,
+    }
+    if let Ok(Version { version }) = format.parse(text) {
+        if version > FORMAT_VERSION {
+            let mut problem = Problem::error("version", format!(
+                "scene is format version {}, but this version of raytrs only understands up to {}",
+                version, FORMAT_VERSION
+            ));
+            problem.location = format.locate(text, "version");
+            return Err(problem);
+        }
+    }
+    format.parse(text)
+}
+
+//the other way around, for scenes built or changed in code. every optional field is filled in,
+//and meshes are written as references to the files they were loaded from, relative to the
+//new scene file where possible. the format is picked from path, like when reading.
+pub fn write_scene(scene: &Scene, path: &Path) -> Result<String, String> {
+    let scn = IpScene::from_scene(scene, path)?;
+    Format::from_path(path).write(&scn)
+}
+
+//files other than scene files that the scene was loaded from: meshes, ies profiles and environment maps
+pub fn referenced_files(scene: &Scene) -> Vec<PathBuf> {
+    fn object_files(object: &dyn SceneObject, files: &mut Vec<PathBuf>) {
+        let any = object.as_any();
+        if let Some(mesh) = any.downcast_ref::<Mesh>() {
+            files.extend(mesh.source.as_ref().map(|source| source.path.clone()));
+        } else if let Some(csg) = any.downcast_ref::<Csg>() {
+            object_files(csg.a.as_ref(), files);
+            object_files(csg.b.as_ref(), files);
+        }
+    }
+    let mut files = Vec::new();
+    for object in &scene.objects {
+        object_files(object.as_ref(), &mut files);
+    }
+    for light in &scene.lights {
+        if let Light::Ies(ies) = light { files.extend(ies.profile.source.clone()); }
+    }
+    if let Sky::Image(map) = &scene.world.sky { files.extend(map.source.clone()); }
+    files
+}
+
+//objects, lights and materials come from every file, the camera, background, environment and
+//ambient light only from the main one. materials with the same name are overridden by whichever file comes later
+fn into_scene(files: Vec<SceneFile>) -> Result<Scene, String> {
+    let mut materials = Materials::new();
+    for file in &files {
+        materials.extend(file.scene.materials.clone());
+    }
+    let mut objects: Vec<Box<dyn SceneObject + Send + Sync>> = Vec::new();
+    let mut lights = Vec::new();
+    let mut view = None;
+    for file in files {
+        let SceneFile { scene, path, name, .. } = file;
+        for (i, object) in scene.objects.into_iter().enumerate() {
+            objects.push(object.into_object(&path, &materials).map_err(|error| match &name {
+                Some(name) => format!("{}: objects[{}]: {}", name, i, error),
+                None => format!("objects[{}]: {}", i, error),
+            })?);
+        }
+        for (i, light) in scene.lights.into_iter().enumerate() {
+            lights.push(light.into_light(&path).map_err(|error| match &name {
+                Some(name) => format!("{}: lights[{}]: {}", name, i, error),
+                None => format!("lights[{}]: {}", i, error),
+            })?);
+        }
+        view = Some((scene.camera, scene.background_color, scene.environment, scene.ambient, path));
+    }
+    let (ip_camera, background_color, environment, ambient, path) = view.ok_or("no scene to load".to_string())?;
+    let camera = Camera::new( 
+        ip_camera.origin,
+        ip_camera.direction,
+        ip_camera.focal_length
+    );
+    let world = match environment {
+        Some(environment) => environment.into_world(&path).map_err(|error| format!("environment: {}", error))?,
+        None => World::color(background_color),
+    };
+    Ok(Scene::new(objects,lights,camera,world,ambient))
+}
 impl IpScene {
-    fn to_scene(self, path: &Path) -> Result<Scene, String> {
-        let mut objects: Vec<Box<dyn SceneObject + Send + Sync>> = Vec::new();
+    fn from_scene(scene: &Scene, path: &Path) -> Result<IpScene, String> {
+        let mut objects = Vec::new();
         let mut lights = Vec::new();
-        for object in self.objects {
-            match object {
-                IpObject::Sphere(sphere) => {
-                    objects.push(Box::new(Sphere::new(
-                        sphere.center,
-                        sphere.radius,
-                        Material::new(sphere.color, sphere.reflective),
-                    )));
+        for object in &scene.objects {
+            objects.push(IpObject::from_object(object.as_ref(), path)?);
+        }
+        for light in &scene.lights {
+            match light {
+                Light::Point(pointlight) => {
+                    lights.push(IpLight::Point(pointlight.clone()));
+                }
+                Light::Sun(sun) => {
+                    lights.push(IpLight::Sun(sun.clone()));
                 }
-                IpObject::Floor(floor) => {
-                    objects.push(Box::new(Floor::new(
-                        floor.y,
-                        Material::new(floor.color, floor.reflective),
-                    )));
+                Light::Spot(spot) => {
+                    lights.push(IpLight::Spot(spot.clone()));
                 }
-                IpObject::Obj(obj) => {
-                    let obj_path = path.parent().unwrap().join(&obj.filename);
-                    let mut tris = read_obj(
-                        fs::read_to_string(obj_path).map_err(
-                            |error| { format!("error reading \'{}\': {}", obj.filename, error) }
-                        )?
-                        .as_str(),
-                        obj.offset,
-                        obj.scale,
-                        Material::new(obj.color,obj.reflective),
-                    );
-                    objects.append(&mut tris);
+                Light::Ies(ies) => {
+                    let source = ies.profile.source.as_ref().ok_or("ies profiles built in code can't be saved".to_string())?;
+                    lights.push(IpLight::Ies(IpIesLight {
+                        filename: relative_path(source, path), origin: ies.origin, direction: ies.direction,
+                        rotation: ies.rotation, power: ies.power.clone(), size: ies.size,
+                    }));
                 }
             }
         }
-        for light in self.lights {
-            match light {
-                IpLight::Point(pointlight) => {
-                    lights.push(Light::Point(pointlight));
+        let camera = IpCamera {
+            origin: scene.camera.origin,
+            direction: scene.camera.direction,
+            focal_length: scene.camera.length,
+        };
+        let (background_color, environment) = match &scene.world.sky {
+            Sky::Color(color) => (*color, None),
+            _ => (black(), Some(IpEnvironment::from_world(&scene.world, path)?)),
+        };
+        Ok(IpScene {
+            version: FORMAT_VERSION, include: Vec::new(), materials: Materials::new(), objects, lights,
+            camera, background_color, environment, ambient: scene.ambient.clone() })
+    }
+}
+impl IpLight {
+    fn into_light(self, path: &Path) -> Result<Light, String> {
+        let light = match self {
+            IpLight::Point(point) => Light::Point(point),
+            IpLight::Sun(sun) => Light::Sun(sun),
+            IpLight::Spot(spot) => Light::Spot(spot),
+            IpLight::Ies(ies) => {
+                let file = path.parent().unwrap().join(&ies.filename);
+                //ies files are plain ascii, but some have latin-1 characters in their comments
+                let data = fs::read(&file).map_err(
+                    |error| { format!("error reading \'{}\': {}", ies.filename, error) }
+                )?;
+                let mut profile = import::read_ies(&String::from_utf8_lossy(&data)).map_err(
+                    |error| { format!("error loading \'{}\': {}", ies.filename, error) }
+                )?;
+                profile.source = Some(file);
+                Light::Ies(IesLight::new(ies.origin, ies.direction, ies.rotation, ies.power, ies.size, profile))
+            }
+        };
+        Ok(light)
+    }
+}
+impl IpEnvironment {
+    fn lighting(&self) -> &IpLighting {
+        match self {
+            IpEnvironment::Image(image) => &image.lighting,
+            IpEnvironment::Gradient(gradient) => &gradient.lighting,
+            IpEnvironment::Sky(sky) => &sky.lighting,
+        }
+    }
+    fn into_world(self, path: &Path) -> Result<World, String> {
+        let (sky, lighting) = match self {
+            IpEnvironment::Image(image) => {
+                let file = path.parent().unwrap().join(&image.filename);
+                let data = fs::read(&file).map_err(
+                    |error| { format!("error reading '{}': {}", image.filename, error) }
+                )?;
+                let mut map = import::read_environment(&data).map_err(
+                    |error| { format!("error loading '{}': {}", image.filename, error) }
+                )?;
+                map.source = Some(file);
+                (Sky::Image(map), image.lighting)
+            }
+            IpEnvironment::Gradient(gradient) => {
+                (Sky::Gradient { zenith: gradient.zenith, horizon: gradient.horizon, ground: gradient.ground }, gradient.lighting)
+            }
+            IpEnvironment::Sky(sky) => {
+                let physical = PhysicalSky::new(sky.elevation, sky.azimuth, sky.turbidity, sky.ground_albedo, sky.sun);
+                (Sky::Physical(physical), sky.lighting)
+            }
+        };
+        Ok(World::new(sky, lighting.rotation, lighting.intensity, lighting.samples))
+    }
+    fn from_world(world: &World, path: &Path) -> Result<IpEnvironment, String> {
+        let lighting = IpLighting { rotation: world.rotation, intensity: world.strength, samples: world.samples };
+        match &world.sky {
+            Sky::Image(map) => {
+                let source = map.source.as_ref().ok_or("environment maps built in code can't be saved".to_string())?;
+                Ok(IpEnvironment::Image(IpEnvImage { filename: relative_path(source, path), lighting }))
+            }
+            Sky::Gradient { zenith, horizon, ground } => {
+                Ok(IpEnvironment::Gradient(IpGradient { zenith: *zenith, horizon: *horizon, ground: *ground, lighting }))
+            }
+            Sky::Physical(sky) => {
+                Ok(IpEnvironment::Sky(IpPhysicalSky {
+                    elevation: sky.elevation, azimuth: sky.azimuth, turbidity: sky.turbidity,
+                    ground_albedo: sky.ground_albedo, sun: sky.sun, lighting,
+                }))
+            }
+            Sky::Color(_) => Err("a plain color isn't an environment".to_string()),
+        }
+    }
+}
+impl IpObject {
+    fn into_object(self, path: &Path, materials: &Materials) -> Result<Box<dyn SceneObject + Send + Sync>, String> {
+        let object: Box<dyn SceneObject + Send + Sync> = match self {
+            IpObject::Sphere(sphere) => {
+                Box::new(Sphere::new(
+                    sphere.center,
+                    sphere.radius,
+                    sphere.surface.material(materials)?,
+                ))
+            }
+            IpObject::Floor(floor) => {
+                //a floor is just a plane at a given height, facing up unless told otherwise
+                let normal = floor.normal;
+                Box::new(Plane::new(
+                    Frame::new(normal.unit() * floor.y, Matrix3::from_axis(normal)),
+                    None,
+                    floor.two_sided,
+                    floor.surface.material(materials)?,
+                ))
+            }
+            IpObject::Plane(plane) => {
+                let basis = match plane.tangent {
+                    Some(tangent) => Matrix3::from_axis_tangent(plane.normal, tangent),
+                    None => Matrix3::from_axis(plane.normal),
+                };
+                let extents = match (plane.width, plane.height) {
+                    (Some(width), Some(height)) => Some((width, height)),
+                    (None, None) => None,
+                    _ => { return Err("bounded planes need both a width and a height".to_string()); }
+                };
+                Box::new(Plane::new(
+                    Frame::new(plane.point, basis),
+                    extents,
+                    plane.two_sided,
+                    plane.surface.material(materials)?,
+                ))
+            }
+            IpObject::Disk(disk) => {
+                Box::new(Disk::new(
+                    disk.center,
+                    disk.normal,
+                    disk.radius,
+                    disk.two_sided,
+                    disk.surface.material(materials)?,
+                ))
+            }
+            IpObject::Box(cuboid) => {
+                let rotation = Matrix3::rotation(cuboid.rotation);
+                Box::new(Cuboid::new(
+                    cuboid.center,
+                    cuboid.size,
+                    rotation,
+                    cuboid.surface.material(materials)?,
+                ))
+            }
+            IpObject::Cylinder(cylinder) => {
+                Box::new(Cylinder::new(
+                    cylinder.base,
+                    cylinder.axis,
+                    cylinder.radius,
+                    cylinder.height,
+                    cylinder.surface.material(materials)?,
+                ))
+            }
+            IpObject::Cone(cone) => {
+                Box::new(Cone::new(
+                    cone.base,
+                    cone.axis,
+                    cone.radius,
+                    cone.height,
+                    cone.surface.material(materials)?,
+                ))
+            }
+            IpObject::Torus(torus) => {
+                Box::new(Torus::new(
+                    torus.center,
+                    torus.axis,
+                    torus.major_radius,
+                    torus.minor_radius,
+                    torus.surface.material(materials)?,
+                ))
+            }
+            IpObject::Sdf(sdf) => {
+                Box::new(SdfObject::new(
+                    sdf.shape,
+                    sdf.max_distance,
+                    sdf.step_scale,
+                    sdf.surface.material(materials)?,
+                ))
+            }
+            IpObject::Obj(obj) => {
+                let obj_path = path.parent().unwrap().join(&obj.filename);
+                let mut mesh = import::read_obj(
+                    fs::read_to_string(&obj_path).map_err(
+                        |error| { format!("error reading \'{}\': {}", obj.filename, error) }
+                    )?
+                    .as_str(),
+                    &obj_path,
+                    obj.offset,
+                    obj.scale,
+                    obj.surface.material(materials)?,
+                    obj.use_mtl,
+                ).map_err(
+                    |error| { format!("error parsing \'{}\': {}", obj.filename, error) }
+                )?;
+                mesh.source = Some(MeshSource {
+                    format: MeshFormat::Obj, path: obj_path, offset: obj.offset, scale: obj.scale,
+                    use_mtl: obj.use_mtl,
+                });
+                Box::new(mesh)
+            }
+            IpObject::Ply(ply) => {
+                let (data, material) = ply.read(path, materials)?;
+                let mut mesh = import::read_ply(&data, ply.offset, ply.scale, material).map_err(
+                    |error| { format!("error parsing \'{}\': {}", ply.filename, error) }
+                )?;
+                mesh.source = Some(ply.source(path, MeshFormat::Ply));
+                Box::new(mesh)
+            }
+            IpObject::Stl(stl) => {
+                let (data, material) = stl.read(path, materials)?;
+                let mut mesh = import::read_stl(&data, stl.offset, stl.scale, material).map_err(
+                    |error| { format!("error parsing \'{}\': {}", stl.filename, error) }
+                )?;
+                mesh.source = Some(stl.source(path, MeshFormat::Stl));
+                Box::new(mesh)
+            }
+            IpObject::Gltf(gltf) => {
+                let (data, material) = gltf.read(path, materials)?;
+                let source = gltf.source(path, MeshFormat::Gltf);
+                let mut mesh = import::read_gltf(&data, &source.path, gltf.offset, gltf.scale, material).map_err(
+                    |error| { format!("error parsing \'{}\': {}", gltf.filename, error) }
+                )?;
+                mesh.source = Some(source);
+                Box::new(mesh)
+            }
+            IpObject::Csg(csg) => {
+                let op = csg.op;
+                let mut operands = Vec::new();
+                for object in csg.objects {
+                    if !object.is_closed() {
+                        return Err("csg can only combine closed objects (sphere, box, cylinder, cone, torus, csg)".to_string());
+                    }
+                    operands.push(object.into_object(path, materials)?);
                 }
+                if operands.len() < 2 {
+                    return Err("csg needs at least two objects to combine".to_string());
+                }
+                //more than two objects are combined left to right, eg. a - b - c
+                let mut operands = operands.into_iter();
+                let first = operands.next().unwrap();
+                operands.fold(first, |a, b| Box::new(Csg::new(op, a, b)))
             }
+        };
+        Ok(object)
+    }
+    fn from_object(object: &dyn SceneObject, path: &Path) -> Result<IpObject, String> {
+        let any = object.as_any();
+        if let Some(sphere) = any.downcast_ref::<Sphere>() {
+            let m = sphere.material;
+            return Ok(IpObject::Sphere(IpSphere {
+                center: sphere.center, radius: sphere.radius, surface: IpSurface::inline(m),
+            }));
+        }
+        //floors were turned into planes on the way in, so they come back out as planes
+        if let Some(plane) = any.downcast_ref::<Plane>() {
+            let m = plane.material;
+            return Ok(IpObject::Plane(IpPlane {
+                point: plane.frame.origin,
+                normal: plane.frame.basis.b,
+                tangent: Some(plane.frame.basis.a),
+                width: plane.extents.map(|e| e.0),
+                height: plane.extents.map(|e| e.1),
+                two_sided: plane.two_sided,
+                surface: IpSurface::inline(m),
+            }));
+        }
+        if let Some(disk) = any.downcast_ref::<Disk>() {
+            let m = disk.material;
+            return Ok(IpObject::Disk(IpDisk {
+                center: disk.frame.origin, normal: disk.frame.basis.b, radius: disk.radius,
+                two_sided: disk.two_sided, surface: IpSurface::inline(m),
+            }));
+        }
+        if let Some(cuboid) = any.downcast_ref::<Cuboid>() {
+            let m = cuboid.material;
+            return Ok(IpObject::Box(IpBox {
+                center: cuboid.frame.origin, size: cuboid.half_size * 2.0,
+                rotation: cuboid.frame.basis.euler_degrees(), surface: IpSurface::inline(m),
+            }));
+        }
+        if let Some(cylinder) = any.downcast_ref::<Cylinder>() {
+            let m = cylinder.material;
+            return Ok(IpObject::Cylinder(IpCylinder {
+                base: cylinder.frame.origin, axis: cylinder.frame.basis.b, radius: cylinder.radius,
+                height: cylinder.height, surface: IpSurface::inline(m),
+            }));
+        }
+        if let Some(cone) = any.downcast_ref::<Cone>() {
+            let m = cone.material;
+            return Ok(IpObject::Cone(IpCone {
+                base: cone.frame.origin, axis: cone.frame.basis.b, radius: cone.radius,
+                height: cone.height, surface: IpSurface::inline(m),
+            }));
+        }
+        if let Some(torus) = any.downcast_ref::<Torus>() {
+            let m = torus.material;
+            return Ok(IpObject::Torus(IpTorus {
+                center: torus.frame.origin, axis: torus.frame.basis.b, major_radius: torus.major_radius,
+                minor_radius: torus.minor_radius, surface: IpSurface::inline(m),
+            }));
+        }
+        if let Some(sdf) = any.downcast_ref::<SdfObject>() {
+            let m = sdf.material;
+            return Ok(IpObject::Sdf(IpSdf {
+                shape: sdf.root.clone(), max_distance: sdf.max_distance, step_scale: sdf.step_scale,
+                surface: IpSurface::inline(m),
+            }));
+        }
+        if let Some(csg) = any.downcast_ref::<Csg>() {
+            return Ok(IpObject::Csg(IpCsg {
+                op: csg.op,
+                objects: vec![IpObject::from_object(csg.a.as_ref(), path)?, IpObject::from_object(csg.b.as_ref(), path)?],
+            }));
+        }
+        if let Some(mesh) = any.downcast_ref::<Mesh>() {
+            let source = mesh.source.as_ref().ok_or(
+                "meshes built in code can't be saved, only ones loaded from a file".to_string()
+            )?;
+            let m = mesh.materials[0];
+            let filename = relative_path(&source.path, path);
+            let file = IpMeshFile {
+                surface: IpSurface::inline(m), filename, offset: source.offset, scale: source.scale,
+            };
+            return Ok(match source.format {
+                MeshFormat::Obj => IpObject::Obj(Obj {
+                    surface: file.surface, filename: file.filename,
+                    offset: file.offset, scale: file.scale, use_mtl: source.use_mtl,
+                }),
+                MeshFormat::Ply => IpObject::Ply(file),
+                MeshFormat::Stl => IpObject::Stl(file),
+                MeshFormat::Gltf => IpObject::Gltf(file),
+            });
         }
-        let camera = Camera::new( 
-            self.camera.origin,
-            self.camera.direction,
-            self.camera.focal_length
-        );
-        let world = World::new(
-            self.background_color,
-            1.0,
-        );
-        Ok(Scene::new(objects,lights,camera,world))
+        Err("scene contains an object that can't be saved".to_string())
+    }
+    fn is_closed(&self) -> bool {
+        matches!(self,
+            IpObject::Sphere(_) | IpObject::Box(_) | IpObject::Cylinder(_) |
+            IpObject::Cone(_) | IpObject::Torus(_) | IpObject::Csg(_)
+        )
     }
 }
-#[derive(Deserialize)]
+//the version of the scene format written by this version of raytrs. files without a version
+//are assumed to be this one, so bump it when the format changes in a way older files won't
+//parse under, and upgrade them in parse_scene
+pub const FORMAT_VERSION: u32 = 1;
+
+//anything left out of a scene file gets these defaults. colors default to white and
+//reflective to false everywhere, vectors to the origin unless said otherwise
+fn current_version() -> u32 { FORMAT_VERSION }
+fn white() -> Color { Color::new(255,255,255,255) }
+fn black() -> Color { Color::new(0,0,0,255) }
+fn yes() -> bool { true }
+fn one() -> f64 { 1.0 }
+fn ones() -> Vec3 { Vec3::new(1.0,1.0,1.0) }
+fn up() -> Vec3 { Vec3::new(0.0,1.0,0.0) }
+fn down() -> Vec3 { Vec3::new(0.0,-1.0,0.0) }
+fn forward() -> Vec3 { Vec3::new(0.0,0.0,1.0) }
+fn origin() -> Vec3 { Vec3::new(0.0,0.0,0.0) }
+
+#[derive(Deserialize,Serialize)]
 struct IpScene {
+    #[serde(default = "current_version")]
+    version: u32,
+    //other scene files to take objects, lights and materials from, relative to this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    materials: Materials,
+    #[serde(default)]
     objects: Vec<IpObject>,
+    #[serde(default)]
     lights: Vec<IpLight>,
+    #[serde(default)]
     camera: IpCamera,
+    #[serde(default = "black")]
     background_color: Color,
+    //light from all around, which replaces background_color when there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    environment: Option<IpEnvironment>,
+    //color, strength, radius and samples default to white, 0.1, 1 and 16
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ambient: Option<Ambient>,
 }
-#[derive(Deserialize)]
+#[derive(Deserialize,Serialize)]
 enum IpObject {
     #[serde(rename = "sphere")]
     Sphere(IpSphere),
     #[serde(rename = "floor")]
     Floor(IpFloor),
+    #[serde(rename = "plane")]
+    Plane(IpPlane),
+    #[serde(rename = "disk")]
+    Disk(IpDisk),
+    #[serde(rename = "box")]
+    Box(IpBox),
+    #[serde(rename = "cylinder")]
+    Cylinder(IpCylinder),
+    #[serde(rename = "cone")]
+    Cone(IpCone),
+    #[serde(rename = "torus")]
+    Torus(IpTorus),
+    #[serde(rename = "sdf")]
+    Sdf(IpSdf),
     #[serde(rename = "obj")]
     Obj(Obj),
+    #[serde(rename = "ply")]
+    Ply(IpMeshFile),
+    #[serde(rename = "stl")]
+    Stl(IpMeshFile),
+    #[serde(rename = "gltf")]
+    Gltf(IpMeshFile), //.gltf or .glb
+    #[serde(rename = "csg")]
+    Csg(IpCsg),
+}
+type Materials = BTreeMap<String, IpMaterial>;
+#[derive(Deserialize,Serialize,Clone)]
+struct IpMaterial {
+    #[serde(default = "white")]
+    color: Color,
+    #[serde(default)]
+    reflective: bool,
+    #[serde(default = "black")]
+    specular: Color, //color of highlights, black for none
+    #[serde(default = "half")]
+    roughness: f64,
+    //blinn-phong exponent, as in mtl files, used in place of roughness
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shininess: Option<f64>,
+    #[serde(default)]
+    transparency: f64, //only for the shadows it casts so far
+}
+fn half() -> f64 { 0.5 }
+//what an object is made of: either the name of one of the scene's materials, or a material
+//of its own written out in the object
+#[derive(Deserialize,Serialize)]
+struct IpSurface {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    material: Option<String>,
+    #[serde(flatten)]
+    inline: IpMaterial,
+}
+impl IpSurface {
+    //materials don't remember their names once loaded, so saved scenes write them out in full
+    fn inline(material: Material) -> IpSurface {
+        IpSurface { material: None, inline: IpMaterial {
+            color: material.color, reflective: material.reflective,
+            specular: material.specular, roughness: material.roughness, shininess: None,
+            transparency: material.transparency,
+        } }
+    }
+    fn material(&self, materials: &Materials) -> Result<Material, String> {
+        let material = match &self.material {
+            Some(name) => materials.get(name).ok_or(format!("there's no material named \'{}\'", name))?,
+            None => &self.inline,
+        };
+        let roughness = material.shininess.map_or(material.roughness, Material::shininess_roughness);
+        Ok(Material::new(material.color, material.reflective).with_specular(material.specular, roughness)
+            .with_transparency(material.transparency))
+    }
 }
-#[derive(Deserialize)]
+#[derive(Deserialize,Serialize)]
 enum IpLight {
     #[serde(rename = "point")]
-    Point(PointLight),
+    Point(PointLight), //strength defaults to 1, color to white and size to 0
+    #[serde(rename = "sun")]
+    Sun(SunLight), //strength defaults to 1, color to white and angle to 0
+    #[serde(rename = "spot")]
+    Spot(SpotLight), //points straight down, with angles of 20 and 30 degrees
+    #[serde(rename = "ies")]
+    Ies(IpIesLight),
+}
+#[derive(Deserialize,Serialize)]
+struct IpIesLight {
+    filename: String,
+    #[serde(default = "origin")]
+    origin: Vec3,
+    #[serde(default = "down")]
+    direction: Vec3,
+    #[serde(default)]
+    rotation: f64,
+    #[serde(flatten)]
+    power: LightPower, //watts or candela are for the profile's brightest direction
+    #[serde(default)]
+    size: f64,
+}
+#[derive(Deserialize,Serialize)]
+enum IpEnvironment {
+    #[serde(rename = "image")]
+    Image(IpEnvImage), //equirectangular .hdr or .png
+    #[serde(rename = "gradient")]
+    Gradient(IpGradient),
+    #[serde(rename = "sky")]
+    Sky(IpPhysicalSky), //daylight from the sun's position, with a sun to match
+}
+#[derive(Deserialize,Serialize)]
+struct IpEnvImage {
+    filename: String,
+    #[serde(flatten)]
+    lighting: IpLighting,
+}
+#[derive(Deserialize,Serialize)]
+struct IpGradient {
+    #[serde(default = "sky_blue")]
+    zenith: Color,
+    #[serde(default = "haze")]
+    horizon: Color,
+    #[serde(default = "dirt")]
+    ground: Color,
+    #[serde(flatten)]
+    lighting: IpLighting,
+}
+#[derive(Deserialize,Serialize)]
+struct IpPhysicalSky {
+    #[serde(default = "forty_five")]
+    elevation: f64, //degrees above the horizon
+    #[serde(default)]
+    azimuth: f64, //degrees from +z toward +x
+    #[serde(default = "three")]
+    turbidity: f64,
+    #[serde(default = "ground_grey")]
+    ground_albedo: Color,
+    #[serde(default = "yes")]
+    sun: bool,
+    #[serde(flatten)]
+    lighting: IpLighting,
 }
-#[derive(Deserialize)]
+fn forty_five() -> f64 { 45.0 }
+fn three() -> f64 { 3.0 }
+fn ground_grey() -> Color { Color::new(77,77,77,255) }
+fn sky_blue() -> Color { Color::new(70,120,210,255) }
+fn haze() -> Color { Color::new(200,220,240,255) }
+fn dirt() -> Color { Color::new(80,70,60,255) }
+#[derive(Deserialize,Serialize)]
+struct IpLighting {
+    #[serde(default)]
+    rotation: f64, //degrees around the y axis
+    #[serde(default = "one")]
+    intensity: f64,
+    #[serde(default = "sixteen")]
+    samples: usize, //rays per diffuse hit, 0 to only show it behind things and in reflections
+}
+fn sixteen() -> usize { 16 }
+#[derive(Deserialize,Serialize)]
 struct IpCamera {
+    #[serde(default = "origin")]
     origin: Vec3,
+    #[serde(default = "forward")]
     direction: Vec3,
+    #[serde(default = "one")]
     focal_length: f64,
 }
-#[derive(Deserialize)]
+impl Default for IpCamera {
+    fn default() -> IpCamera {
+        IpCamera { origin: origin(), direction: forward(), focal_length: one() }
+    }
+}
+#[derive(Deserialize,Serialize)]
 struct IpSphere {
+    #[serde(default = "origin")]
     center: Vec3,
+    #[serde(default = "one")]
     radius: f64,
-    reflective: bool,
-    color: Color,
+    #[serde(flatten)]
+    surface: IpSurface,
 }
-#[derive(Deserialize)]
+#[derive(Deserialize,Serialize)]
 struct IpFloor {
-    y: f64,
-    color: Color,
-    reflective: bool,
+    #[serde(default)]
+    y: f64, //distance from the origin along the normal
+    #[serde(default = "up")]
+    normal: Vec3,
+    //floors from before planes were only ever lit and seen from above, so they stay that way
+    #[serde(default)]
+    two_sided: bool,
+    #[serde(flatten)]
+    surface: IpSurface,
+}
+#[derive(Deserialize,Serialize)]
+struct IpPlane {
+    #[serde(default = "origin")]
+    point: Vec3,
+    #[serde(default = "up")]
+    normal: Vec3,
+    //direction of the width edge of bounded planes. picked automatically if left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tangent: Option<Vec3>,
+    //unbounded if both are left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    width: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<f64>,
+    #[serde(default = "yes")]
+    two_sided: bool,
+    #[serde(flatten)]
+    surface: IpSurface,
+}
+#[derive(Deserialize,Serialize)]
+struct IpDisk {
+    #[serde(default = "origin")]
+    center: Vec3,
+    #[serde(default = "up")]
+    normal: Vec3,
+    #[serde(default = "one")]
+    radius: f64,
+    #[serde(default = "yes")]
+    two_sided: bool,
+    #[serde(flatten)]
+    surface: IpSurface,
+}
+#[derive(Deserialize,Serialize)]
+struct IpBox {
+    #[serde(default = "origin")]
+    center: Vec3,
+    #[serde(default = "ones")]
+    size: Vec3,
+    #[serde(default = "origin")]
+    rotation: Vec3, //euler angles in degrees
+    #[serde(flatten)]
+    surface: IpSurface,
+}
+#[derive(Deserialize,Serialize)]
+struct IpCylinder {
+    #[serde(default = "origin")]
+    base: Vec3,
+    #[serde(default = "up")]
+    axis: Vec3,
+    #[serde(default = "one")]
+    radius: f64,
+    #[serde(default = "one")]
+    height: f64,
+    #[serde(flatten)]
+    surface: IpSurface,
+}
+#[derive(Deserialize,Serialize)]
+struct IpCone {
+    #[serde(default = "origin")]
+    base: Vec3,
+    #[serde(default = "up")]
+    axis: Vec3,
+    #[serde(default = "one")]
+    radius: f64, //radius of the base, the tip is at height along axis
+    #[serde(default = "one")]
+    height: f64,
+    #[serde(flatten)]
+    surface: IpSurface,
+}
+#[derive(Deserialize,Serialize)]
+struct IpTorus {
+    #[serde(default = "origin")]
+    center: Vec3,
+    #[serde(default = "up")]
+    axis: Vec3,
+    #[serde(default = "one")]
+    major_radius: f64,
+    #[serde(default = "quarter")]
+    minor_radius: f64,
+    #[serde(flatten)]
+    surface: IpSurface,
+}
+fn quarter() -> f64 { 0.25 }
+#[derive(Deserialize,Serialize)]
+struct IpSdf {
+    shape: SdfNode,
+    #[serde(default = "hundred")]
+    max_distance: f64, //how far rays march before giving up
+    #[serde(default = "one")]
+    step_scale: f64, //lower this if twisted shapes have holes in them
+    #[serde(flatten)]
+    surface: IpSurface,
 }
-#[derive(Deserialize)]
+fn hundred() -> f64 { 100.0 }
+#[derive(Deserialize,Serialize)]
+struct IpCsg {
+    op: CsgOp,
+    objects: Vec<IpObject>,
+}
+#[derive(Deserialize,Serialize)]
 struct Obj {
-    color: Color,
-    reflective: bool,
     filename: String,
+    #[serde(default = "origin")]
     offset: Vec3,
+    #[serde(default = "ones")]
     scale: Vec3,
+    #[serde(default)]
+    use_mtl: bool, //take materials from the obj's mtl file instead of color/reflective
+    #[serde(flatten)]
+    surface: IpSurface,
 }
-
-
-fn read_obj(contents: &str, offset: Vec3, scale: Vec3, material: Material) -> Vec<Box<dyn SceneObject + Send + Sync>> {
-    let mut verts: Vec<Vec3> = Vec::new();
-    let mut norms: Vec<Vec3> = Vec::new();
-    let mut tris: Vec<Box<dyn SceneObject + Send + Sync>> = Vec::new();
-    
-    for line in contents.lines() {
-        let is_vert = line.find("v ");
-        if is_vert.is_some() { 
-            let values: Vec<&str> = line.split(' ').collect();
-            let x = values[1].parse::<f64>().unwrap();
-            let y = values[2].parse::<f64>().unwrap();
-            let z = values[3].parse::<f64>().unwrap();
-            verts.push(Vec3::new(x,y,z));
-        }
-        let is_norm = line.find("vn ");
-        if is_norm.is_some() { 
-            let values: Vec<&str> = line.split(' ').collect();
-            let x = values[1].parse::<f64>().unwrap();
-            let y = values[2].parse::<f64>().unwrap();
-            let z = values[3].parse::<f64>().unwrap();
-            norms.push(Vec3::new(x,y,z));
-        }
-        
-        let is_face = line.find("f ");
-        if is_face.is_some() { 
-            let values: Vec<&str> = line.split(' ').collect();
-            let mut i = Vec::new();
-            for value in &values[1..] {
-                if value.is_empty() == false {
-                    let ind: Vec<&str> = value.split('/').collect();
-                    i.push( ind[0].parse::<usize>().unwrap()-1 );
-                }
-            }
-            let mut n = Vec::new();
-            for value in &values[1..] {
-                if value.is_empty() == false {
-                    let ind: Vec<&str> = value.split('/').collect();
-                    n.push( ind[2].parse::<usize>().unwrap()-1 );
-                }
-            }
-            tris.push( Box::new(Tri::new(
-                verts[i[0]] * scale + offset,
-                verts[i[1]] * scale + offset,
-                verts[i[2]] * scale + offset,
-                norms[n[0]],norms[n[1]],norms[n[2]],
-                material
-            )));
-
-            if i.len() > 3 { //quad
-                tris.push( Box::new(Tri::new(
-                    verts[i[0]] * scale + offset,
-                    verts[i[2]] * scale + offset,
-                    verts[i[3]] * scale + offset,
-                    norms[n[0]],norms[n[2]],norms[n[3]],
-                    material
-                )));
-            }
+//any other mesh file. color and reflective are used where the file has no material of its own
+#[derive(Deserialize,Serialize)]
+struct IpMeshFile {
+    filename: String,
+    #[serde(default = "origin")]
+    offset: Vec3,
+    #[serde(default = "ones")]
+    scale: Vec3,
+    #[serde(flatten)]
+    surface: IpSurface,
+}
+impl IpMeshFile {
+    fn read(&self, path: &Path, materials: &Materials) -> Result<(Vec<u8>, Material), String> {
+        let data = fs::read(path.parent().unwrap().join(&self.filename)).map_err(
+            |error| { format!("error reading \'{}\': {}", self.filename, error) }
+        )?;
+        Ok((data, self.surface.material(materials)?))
+    }
+    fn source(&self, path: &Path, format: MeshFormat) -> MeshSource {
+        MeshSource {
+            format, path: path.parent().unwrap().join(&self.filename),
+            offset: self.offset, scale: self.scale, use_mtl: false,
         }
     }
-    return tris;
 }
+//path of file as seen from the directory scene_file is in, or an absolute path if it's not
+//under that directory
+fn relative_path(file: &Path, scene_file: &Path) -> String {
+    let dir = scene_file.parent().unwrap_or(Path::new("."));
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let file = fs::canonicalize(file).unwrap_or(file.to_path_buf());
+    let relative = match fs::canonicalize(dir) {
+        Ok(dir) => file.strip_prefix(dir).unwrap_or(&file).to_path_buf(),
+        Err(_) => file.clone(),
+    };
+    relative.to_string_lossy().into_owned()
+}
+
 
 pub const DEFAULT_JSON: &str = r#"
 {
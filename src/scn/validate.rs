@@ -0,0 +1,605 @@
+//checks a parsed scene for anything that would fail to load or render nonsense, before
+//any of it gets loaded. every problem is collected rather than stopping at the first one,
+//and each is reported with its path in the scene (eg. objects[3].sphere.radius) and
+//where that is in the file.
+
+use std::fmt;
+use std::path::Path;
+use std::collections::BTreeSet;
+
+use super::*;
+use super::serde::de::{self, Deserializer, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+pub struct Problem {
+    pub path: String, //empty for problems with the file as a whole
+    pub message: String,
+    pub is_error: bool, //warnings don't stop the scene from rendering
+    pub location: Option<(usize,usize)>, //line and column
+    pub file: Option<String>, //for problems in included files
+}
+impl Problem {
+    pub fn error(path: &str, message: String) -> Problem
+    { Problem { path: path.to_string(), message, is_error: true, location: None, file: None } }
+    pub fn warning(path: &str, message: String) -> Problem
+    { Problem { path: path.to_string(), message, is_error: false, location: None, file: None } }
+}
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", if self.is_error { "error" } else { "warning" })?;
+        if let Some(file) = &self.file { write!(f, " in {}", file)?; }
+        if !self.path.is_empty() { write!(f, " at {}", self.path)?; }
+        if let Some((line, column)) = self.location { write!(f, " (line {}, column {})", line, column)?; }
+        write!(f, ": {}", self.message)
+    }
+}
+
+//every problem with the scene and the files it includes, located in the text they were parsed from
+pub fn check(files: &[SceneFile]) -> Vec<Problem> {
+    let materials: BTreeSet<String> = files.iter().flat_map(|file| file.scene.materials.keys().cloned()).collect();
+    let mut problems = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        let mut checker = Checker {
+            problems: Vec::new(),
+            dir: file.path.parent().unwrap_or(Path::new(".")),
+            materials: &materials,
+        };
+        checker.scene(&file.scene);
+        //the main file is last, and the only one whose camera gets used
+        if i == files.len() - 1 {
+            checker.camera(&file.scene.camera);
+            if let Some(environment) = &file.scene.environment {
+                checker.environment(environment);
+            }
+            if let Some(ambient) = &file.scene.ambient {
+                checker.non_negative("ambient.strength", ambient.strength);
+                checker.positive("ambient.radius", ambient.radius);
+            }
+            if files.iter().all(|file| file.scene.objects.is_empty()) {
+                checker.warning("objects", "scene has no objects".to_string());
+            }
+            let sky_light = file.scene.environment.as_ref().is_some_and(|environment| environment.lighting().samples > 0);
+            let ambient_light = file.scene.ambient.as_ref().is_some_and(|ambient| ambient.strength > 0.0);
+            if files.iter().all(|file| file.scene.lights.is_empty()) && !sky_light && !ambient_light {
+                checker.warning("lights", "scene has no lights, every object will be black".to_string());
+            }
+        } else {
+            if file.scene.environment.is_some() {
+                checker.warning("environment", "only the main scene file's environment is used".to_string());
+            }
+            if file.scene.ambient.is_some() {
+                checker.warning("ambient", "only the main scene file's ambient light is used".to_string());
+            }
+        }
+        for mut problem in checker.problems {
+            problem.location = file.format.locate(&file.text, &problem.path);
+            problem.file = file.name.clone();
+            problems.push(problem);
+        }
+    }
+    problems
+}
+
+struct Checker<'a> {
+    problems: Vec<Problem>,
+    dir: &'a Path, //mesh filenames are relative to this
+    materials: &'a BTreeSet<String>, //names of materials from every file
+}
+impl<'a> Checker<'a> {
+    fn error(&mut self, path: &str, message: String) {
+        self.problems.push(Problem::error(path, message));
+    }
+    fn warning(&mut self, path: &str, message: String) {
+        self.problems.push(Problem::warning(path, message));
+    }
+    fn number(&mut self, path: &str, value: f64) -> bool {
+        if !value.is_finite() { self.error(path, "must be a finite number".to_string()); }
+        value.is_finite()
+    }
+    fn vector(&mut self, path: &str, v: Vec3) -> bool {
+        self.number(&format!("{}.x", path), v.x) &
+        self.number(&format!("{}.y", path), v.y) &
+        self.number(&format!("{}.z", path), v.z)
+    }
+    fn positive(&mut self, path: &str, value: f64) {
+        if self.number(path, value) && value <= 0.0 { self.error(path, "must be greater than zero".to_string()); }
+    }
+    fn non_negative(&mut self, path: &str, value: f64) {
+        if self.number(path, value) && value < 0.0 { self.error(path, "can't be negative".to_string()); }
+    }
+    fn direction(&mut self, path: &str, v: Vec3) {
+        if self.vector(path, v) && v.magn() < 1e-12 { self.error(path, "direction can't be zero".to_string()); }
+    }
+
+    fn surface(&mut self, path: &str, surface: &IpSurface) {
+        if let Some(name) = &surface.material {
+            if !self.materials.contains(name) {
+                self.error(&format!("{}.material", path), format!("there's no material named \'{}\'", name));
+            }
+        } else {
+            self.material(path, &surface.inline);
+        }
+    }
+    fn material(&mut self, path: &str, material: &IpMaterial) {
+        let roughness = format!("{}.roughness", path);
+        if self.number(&roughness, material.roughness) && !(0.0..=1.0).contains(&material.roughness) {
+            self.error(&roughness, "roughness must be from 0 to 1".to_string());
+        }
+        if let Some(shininess) = material.shininess {
+            self.non_negative(&format!("{}.shininess", path), shininess);
+        }
+        let transparency = format!("{}.transparency", path);
+        if self.number(&transparency, material.transparency) && !(0.0..=1.0).contains(&material.transparency) {
+            self.error(&transparency, "transparency must be from 0 to 1".to_string());
+        }
+    }
+
+    fn scene(&mut self, scn: &IpScene) {
+        for (name, material) in &scn.materials {
+            self.material(&format!("materials.{}", name), material);
+        }
+        for (i, object) in scn.objects.iter().enumerate() {
+            self.object(&format!("objects[{}]", i), object);
+        }
+        for (i, light) in scn.lights.iter().enumerate() {
+            match light {
+                IpLight::Point(point) => {
+                    let path = format!("lights[{}].point", i);
+                    self.vector(&format!("{}.origin", path), point.origin);
+                    self.power(&path, &point.power);
+                    self.non_negative(&format!("{}.size", path), point.size);
+                }
+                IpLight::Spot(spot) => {
+                    let path = format!("lights[{}].spot", i);
+                    self.vector(&format!("{}.origin", path), spot.origin);
+                    self.direction(&format!("{}.direction", path), spot.direction);
+                    self.power(&path, &spot.power);
+                    self.non_negative(&format!("{}.size", path), spot.size);
+                    let inner = self.number(&format!("{}.inner_angle", path), spot.inner_angle);
+                    if self.number(&format!("{}.outer_angle", path), spot.outer_angle) {
+                        if !(0.0..=180.0).contains(&spot.outer_angle) {
+                            self.error(&format!("{}.outer_angle", path), "angle must be from 0 to 180 degrees".to_string());
+                        } else if inner && !(0.0..=spot.outer_angle).contains(&spot.inner_angle) {
+                            self.error(&format!("{}.inner_angle", path), "inner angle must be from 0 up to the outer angle".to_string());
+                        }
+                    }
+                }
+                IpLight::Ies(ies) => {
+                    let path = format!("lights[{}].ies", i);
+                    if !self.dir.join(&ies.filename).is_file() {
+                        self.error(&format!("{}.filename", path), format!("can't find \'{}\'", ies.filename));
+                    }
+                    self.vector(&format!("{}.origin", path), ies.origin);
+                    self.direction(&format!("{}.direction", path), ies.direction);
+                    self.number(&format!("{}.rotation", path), ies.rotation);
+                    self.power(&path, &ies.power);
+                    self.non_negative(&format!("{}.size", path), ies.size);
+                }
+                IpLight::Sun(sun) => {
+                    let path = format!("lights[{}].sun", i);
+                    self.direction(&format!("{}.direction", path), sun.direction);
+                    self.non_negative(&format!("{}.strength", path), sun.strength);
+                    if sun.strength == 0.0 { self.warning(&format!("{}.strength", path), "light gives off no light".to_string()); }
+                    if self.number(&format!("{}.angle", path), sun.angle) && !(0.0..180.0).contains(&sun.angle) {
+                        self.error(&format!("{}.angle", path), "angle must be from 0 up to 180 degrees".to_string());
+                    }
+                }
+            }
+        }
+    }
+    fn camera(&mut self, camera: &IpCamera) {
+        self.vector("camera.origin", camera.origin);
+        self.direction("camera.direction", camera.direction);
+        //the camera's up is always +y, so it has nothing to be sideways from when looking along it
+        if camera.direction.x == 0.0 && camera.direction.z == 0.0 && camera.direction.y != 0.0 {
+            self.error("camera.direction", "camera can't look straight up or down".to_string());
+        }
+        self.positive("camera.focal_length", camera.focal_length);
+    }
+
+    fn object(&mut self, path: &str, object: &IpObject) {
+        match object {
+            IpObject::Sphere(sphere) => {
+                let path = format!("{}.sphere", path);
+                self.surface(&path, &sphere.surface);
+                self.vector(&format!("{}.center", path), sphere.center);
+                self.positive(&format!("{}.radius", path), sphere.radius);
+            }
+            IpObject::Floor(floor) => {
+                let path = format!("{}.floor", path);
+                self.surface(&path, &floor.surface);
+                self.number(&format!("{}.y", path), floor.y);
+                self.direction(&format!("{}.normal", path), floor.normal);
+            }
+            IpObject::Plane(plane) => {
+                let path = format!("{}.plane", path);
+                self.surface(&path, &plane.surface);
+                self.vector(&format!("{}.point", path), plane.point);
+                self.direction(&format!("{}.normal", path), plane.normal);
+                if let Some(tangent) = plane.tangent {
+                    self.direction(&format!("{}.tangent", path), tangent);
+                    if tangent.unit().cross(plane.normal.unit()).magn() < 1e-6 {
+                        self.error(&format!("{}.tangent", path), "tangent can't be parallel to the normal".to_string());
+                    }
+                }
+                match (plane.width, plane.height) {
+                    (Some(width), Some(height)) => {
+                        self.positive(&format!("{}.width", path), width);
+                        self.positive(&format!("{}.height", path), height);
+                    }
+                    (None, None) => {}
+                    _ => { self.error(&path, "bounded planes need both a width and a height".to_string()); }
+                }
+            }
+            IpObject::Disk(disk) => {
+                let path = format!("{}.disk", path);
+                self.surface(&path, &disk.surface);
+                self.vector(&format!("{}.center", path), disk.center);
+                self.direction(&format!("{}.normal", path), disk.normal);
+                self.positive(&format!("{}.radius", path), disk.radius);
+            }
+            IpObject::Box(cuboid) => {
+                let path = format!("{}.box", path);
+                self.surface(&path, &cuboid.surface);
+                self.vector(&format!("{}.center", path), cuboid.center);
+                self.positive(&format!("{}.size.x", path), cuboid.size.x);
+                self.positive(&format!("{}.size.y", path), cuboid.size.y);
+                self.positive(&format!("{}.size.z", path), cuboid.size.z);
+                self.vector(&format!("{}.rotation", path), cuboid.rotation);
+            }
+            IpObject::Cylinder(IpCylinder { base, axis, radius, height, surface }) |
+            IpObject::Cone(IpCone { base, axis, radius, height, surface }) => {
+                let kind = if let IpObject::Cone(_) = object { "cone" } else { "cylinder" };
+                let path = format!("{}.{}", path, kind);
+                self.surface(&path, surface);
+                self.vector(&format!("{}.base", path), *base);
+                self.direction(&format!("{}.axis", path), *axis);
+                self.positive(&format!("{}.radius", path), *radius);
+                self.positive(&format!("{}.height", path), *height);
+            }
+            IpObject::Torus(torus) => {
+                let path = format!("{}.torus", path);
+                self.surface(&path, &torus.surface);
+                self.vector(&format!("{}.center", path), torus.center);
+                self.direction(&format!("{}.axis", path), torus.axis);
+                self.positive(&format!("{}.major_radius", path), torus.major_radius);
+                self.positive(&format!("{}.minor_radius", path), torus.minor_radius);
+                if torus.minor_radius >= torus.major_radius {
+                    self.warning(&format!("{}.minor_radius", path), "tube is thicker than the ring, so the torus overlaps itself".to_string());
+                }
+            }
+            IpObject::Sdf(sdf) => {
+                let path = format!("{}.sdf", path);
+                self.surface(&path, &sdf.surface);
+                self.sdf(&format!("{}.shape", path), &sdf.shape);
+                self.positive(&format!("{}.max_distance", path), sdf.max_distance);
+                self.positive(&format!("{}.step_scale", path), sdf.step_scale);
+                if sdf.step_scale > 1.0 {
+                    self.warning(&format!("{}.step_scale", path), "step scales above 1 can step right through surfaces".to_string());
+                }
+            }
+            IpObject::Obj(obj) => {
+                let path = format!("{}.obj", path);
+                self.surface(&path, &obj.surface);
+                self.mesh_file(&path, &obj.filename, obj.scale);
+            }
+            IpObject::Ply(file) | IpObject::Stl(file) | IpObject::Gltf(file) => {
+                let kind = match object { IpObject::Ply(_) => "ply", IpObject::Stl(_) => "stl", _ => "gltf" };
+                let path = format!("{}.{}", path, kind);
+                self.surface(&path, &file.surface);
+                self.mesh_file(&path, &file.filename, file.scale);
+            }
+            IpObject::Csg(csg) => {
+                let path = format!("{}.csg.objects", path);
+                if csg.objects.len() < 2 { self.error(&path, "csg needs at least two objects to combine".to_string()); }
+                for (i, object) in csg.objects.iter().enumerate() {
+                    let path = format!("{}[{}]", path, i);
+                    if !object.is_closed() {
+                        self.error(&path, "csg can only combine closed objects (sphere, box, cylinder, cone, torus, csg)".to_string());
+                    }
+                    self.object(&path, object);
+                }
+            }
+        }
+    }
+    fn power(&mut self, path: &str, power: &LightPower) {
+        let (brightness, name) = match (power.watts, power.candela) {
+            (Some(_), Some(_)) => {
+                self.error(&format!("{}.candela", path), "a light's power can be in watts or candela, not both".to_string());
+                return;
+            }
+            (Some(watts), None) => (watts, "watts"),
+            (None, Some(candela)) => (candela, "candela"),
+            (None, None) => (power.strength, "strength"),
+        };
+        let brightness_path = format!("{}.{}", path, name);
+        self.non_negative(&brightness_path, brightness);
+        if brightness == 0.0 { self.warning(&brightness_path, "light gives off no light".to_string()); }
+        if let Some(radius) = power.falloff_radius {
+            self.positive(&format!("{}.falloff_radius", path), radius);
+        }
+    }
+    fn environment(&mut self, environment: &IpEnvironment) {
+        let path = match environment {
+            IpEnvironment::Image(image) => {
+                if !self.dir.join(&image.filename).is_file() {
+                    self.error("environment.image.filename", format!("can't find \'{}\'", image.filename));
+                }
+                "environment.image"
+            }
+            IpEnvironment::Gradient(_) => "environment.gradient",
+            IpEnvironment::Sky(sky) => {
+                if self.number("environment.sky.elevation", sky.elevation) {
+                    if !(-90.0..=90.0).contains(&sky.elevation) {
+                        self.error("environment.sky.elevation", "elevation must be between -90 and 90 degrees".to_string());
+                    } else if sky.elevation <= 0.0 {
+                        self.warning("environment.sky.elevation", "sun is below the horizon, the sky model is only meant for daytime".to_string());
+                    }
+                }
+                self.number("environment.sky.azimuth", sky.azimuth);
+                //the fit the model is based on only covers these
+                if self.number("environment.sky.turbidity", sky.turbidity) && !(1.7..=10.0).contains(&sky.turbidity) {
+                    self.error("environment.sky.turbidity", "turbidity must be between 1.7 and 10".to_string());
+                }
+                "environment.sky"
+            }
+        };
+        let lighting = environment.lighting();
+        self.number(&format!("{}.rotation", path), lighting.rotation);
+        self.non_negative(&format!("{}.intensity", path), lighting.intensity);
+    }
+    fn mesh_file(&mut self, path: &str, filename: &str, scale: Vec3) {
+        if !self.dir.join(filename).is_file() {
+            self.error(&format!("{}.filename", path), format!("can't find \'{}\'", filename));
+        }
+        if self.vector(&format!("{}.scale", path), scale) && (scale.x == 0.0 || scale.y == 0.0 || scale.z == 0.0) {
+            self.error(&format!("{}.scale", path), "scale can't be zero, the mesh would be flat".to_string());
+        }
+    }
+    fn sdf(&mut self, path: &str, node: &SdfNode) {
+        match node {
+            SdfNode::Sphere { center, radius } => {
+                self.vector(&format!("{}.sphere.center", path), *center);
+                self.positive(&format!("{}.sphere.radius", path), *radius);
+            }
+            SdfNode::Box { center, size, rounding } => {
+                self.vector(&format!("{}.box.center", path), *center);
+                self.vector(&format!("{}.box.size", path), *size);
+                self.non_negative(&format!("{}.box.rounding", path), *rounding);
+            }
+            SdfNode::Torus { center, major_radius, minor_radius } => {
+                self.vector(&format!("{}.torus.center", path), *center);
+                self.positive(&format!("{}.torus.major_radius", path), *major_radius);
+                self.positive(&format!("{}.torus.minor_radius", path), *minor_radius);
+            }
+            SdfNode::Capsule { a, b, radius } => {
+                self.vector(&format!("{}.capsule.a", path), *a);
+                self.vector(&format!("{}.capsule.b", path), *b);
+                self.positive(&format!("{}.capsule.radius", path), *radius);
+            }
+            SdfNode::Union { objects } => { self.sdf_list(&format!("{}.union", path), objects); }
+            SdfNode::Intersection { objects } => { self.sdf_list(&format!("{}.intersection", path), objects); }
+            SdfNode::Subtraction { objects } => { self.sdf_list(&format!("{}.subtraction", path), objects); }
+            SdfNode::SmoothUnion { objects, k } => {
+                self.sdf_list(&format!("{}.smooth_union", path), objects);
+                self.positive(&format!("{}.smooth_union.k", path), *k);
+            }
+            SdfNode::SmoothSubtraction { objects, k } => {
+                self.sdf_list(&format!("{}.smooth_subtraction", path), objects);
+                self.positive(&format!("{}.smooth_subtraction.k", path), *k);
+            }
+            SdfNode::Repeat { period, object } => {
+                let path = format!("{}.repeat", path);
+                if self.vector(&format!("{}.period", path), *period) && (period.x < 0.0 || period.y < 0.0 || period.z < 0.0) {
+                    self.error(&format!("{}.period", path), "period can't be negative".to_string());
+                }
+                self.sdf(&format!("{}.object", path), object);
+            }
+            SdfNode::Twist { rate, object } => {
+                self.number(&format!("{}.twist.rate", path), *rate);
+                self.sdf(&format!("{}.twist.object", path), object);
+            }
+        }
+    }
+    fn sdf_list(&mut self, path: &str, objects: &[SdfNode]) {
+        let path = format!("{}.objects", path);
+        if objects.is_empty() { self.error(&path, "needs at least one object".to_string()); }
+        for (i, object) in objects.iter().enumerate() {
+            self.sdf(&format!("{}[{}]", path, i), object);
+        }
+    }
+}
+
+enum PathPart {
+    Key(String),
+    Index(usize),
+}
+fn split_path(path: &str) -> Vec<PathPart> {
+    let mut parts = Vec::new();
+    for piece in path.split('.').filter(|p| !p.is_empty()) {
+        let mut pieces = piece.split('[');
+        let key = pieces.next().unwrap_or("");
+        if !key.is_empty() { parts.push(PathPart::Key(key.to_string())); }
+        for index in pieces {
+            if let Ok(i) = index.trim_end_matches(']').parse() { parts.push(PathPart::Index(i)); }
+        }
+    }
+    parts
+}
+
+//line and column of the value at path in json text. if the path doesn't exist all the
+//way down (eg. a field that was left out), the closest parent that does is used instead
+pub fn locate(text: &str, path: &str) -> Option<(usize,usize)> {
+    let mut json = JsonCursor { bytes: text.as_bytes(), pos: 0 };
+    json.whitespace();
+    let mut found = json.pos;
+    for part in split_path(path) {
+        match json.find(&part) {
+            Some(pos) => { found = pos; }
+            None => break,
+        }
+    }
+    let before = &text[..found.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    Some((line, column))
+}
+
+//walks down path in any self describing format, and stops where it ends (or where it stops
+//existing) with an error. the format's deserializer marks that error with where it happened,
+//which is the only way to get at where things are in yaml and toml
+pub fn walk<'de, D: Deserializer<'de>>(deserializer: D, path: &str) -> Result<(), D::Error> {
+    Walk { parts: &split_path(path) }.deserialize(deserializer)
+}
+struct Walk<'a> {
+    parts: &'a [PathPart],
+}
+impl<'de, 'a> DeserializeSeed<'de> for Walk<'a> {
+    type Value = ();
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+impl<'de, 'a> Visitor<'de> for Walk<'a> {
+    type Value = ();
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "the end of the path")
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        if let Some((PathPart::Key(key), rest)) = self.parts.split_first() {
+            while let Some(name) = map.next_key::<String>()? {
+                if name == *key { return map.next_value_seed(Walk { parts: rest }); }
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Err(de::Error::custom("here"))
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        if let Some((PathPart::Index(index), rest)) = self.parts.split_first() {
+            for _ in 0..*index {
+                if seq.next_element::<IgnoredAny>()?.is_none() { break; }
+            }
+            if seq.next_element_seed(Walk { parts: rest })?.is_some() { return Ok(()); }
+        }
+        Err(de::Error::custom("here"))
+    }
+    //anything else is as far down as the path goes, and the default visit methods all fail
+}
+
+//just enough of a json reader to walk down to a value and skip over everything else
+struct JsonCursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> JsonCursor<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+    fn whitespace(&mut self) {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.peek() { self.pos += 1; }
+    }
+    fn string(&mut self) -> Option<String> {
+        if self.peek() != Some(b'"') { return None; }
+        self.pos += 1;
+        let start = self.pos;
+        loop {
+            match self.peek()? {
+                b'\\' => { self.pos += 2; }
+                b'"' => break,
+                _ => { self.pos += 1; }
+            }
+        }
+        let string = String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned();
+        self.pos += 1;
+        Some(string)
+    }
+    fn skip(&mut self) -> Option<()> {
+        match self.peek()? {
+            b'"' => { self.string()?; }
+            open @ (b'{' | b'[') => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.pos += 1;
+                loop {
+                    self.whitespace();
+                    match self.peek()? {
+                        c if c == close => { self.pos += 1; break; }
+                        b',' | b':' => { self.pos += 1; }
+                        _ => { self.skip()?; }
+                    }
+                }
+            }
+            _ => {
+                while !matches!(self.peek()?, b',' | b']' | b'}' | b' ' | b'\n' | b'\r' | b'\t') { self.pos += 1; }
+            }
+        }
+        Some(())
+    }
+    //move from the start of an object or array to the start of one of its values
+    fn find(&mut self, part: &PathPart) -> Option<usize> {
+        let object = match part { PathPart::Key(_) => b'{', PathPart::Index(_) => b'[' };
+        if self.peek()? != object { return None; }
+        self.pos += 1;
+        let mut index = 0;
+        loop {
+            self.whitespace();
+            if matches!(self.peek()?, b'}' | b']') { return None; }
+            let matched = match part {
+                PathPart::Key(key) => {
+                    let name = self.string()?;
+                    self.whitespace();
+                    if self.peek()? != b':' { return None; }
+                    self.pos += 1;
+                    name == *key
+                }
+                PathPart::Index(i) => index == *i,
+            };
+            self.whitespace();
+            if matched { return Some(self.pos); }
+            self.skip()?;
+            self.whitespace();
+            if self.peek()? == b',' { self.pos += 1; }
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //where check_scene puts the first error in a scene file named name
+    fn error_at(name: &str, text: &str) -> Option<(String, Option<(usize,usize)>)> {
+        check_scene(text, Path::new(name)).into_iter().find(|problem| problem.is_error)
+            .map(|problem| (problem.path, problem.location))
+    }
+
+    #[test]
+    fn json_paths_are_located() {
+        let text = "{\n  \"objects\": [\n    { \"sphere\": { \"radius\": 1 } },\n    { \"sphere\": {\n      \"radius\": -2 } }\n  ]\n}";
+        assert_eq!(locate(text, "objects[1].sphere.radius"), Some((5, 17)));
+        assert_eq!(locate(text, "objects[0].sphere"), Some((3, 17)));
+        //paths that don't exist, like a missing field, fall back to the closest thing that does
+        assert_eq!(locate(text, "objects[1].sphere.center"), Some((4, 17)));
+        assert_eq!(error_at("scene.json", text), Some(("objects[1].sphere.radius".to_string(), Some((5, 17)))));
+    }
+
+    #[test]
+    fn yaml_and_toml_paths_are_located() {
+        let yaml = "lights: []\nobjects:\n  - sphere:\n      radius: 1\n  - sphere:\n      radius: -2\n";
+        assert_eq!(error_at("scene.yaml", yaml), Some(("objects[1].sphere.radius".to_string(), Some((6, 15)))));
+        let toml = "lights = []\n\n[[objects]]\n[objects.sphere]\nradius = 1.0\n\n[[objects]]\n[objects.sphere]\nradius = -2.0\n";
+        assert_eq!(error_at("scene.toml", toml), Some(("objects[1].sphere.radius".to_string(), Some((9, 10)))));
+    }
+
+    #[test]
+    fn paths_are_split_into_keys_and_indices() {
+        let parts = split_path("objects[3].csg.objects[0][1].radius");
+        let expected = ["objects", "3", "csg", "objects", "0", "1", "radius"];
+        assert_eq!(parts.len(), expected.len());
+        for (part, expected) in parts.iter().zip(expected) {
+            match part {
+                PathPart::Key(key) => assert_eq!(key, expected),
+                PathPart::Index(index) => assert_eq!(index.to_string(), expected),
+            }
+        }
+    }
+}
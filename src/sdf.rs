@@ -0,0 +1,149 @@
+//implicit surfaces described by signed distance functions, rendered by sphere tracing:
+//the distance function says how far the nearest surface is, so the ray can safely
+//step that far forward, until it's close enough to count as a hit.
+
+extern crate serde;
+use self::serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+use crate::*;
+
+const HIT_DISTANCE: f64 = 0.0001;
+const MAX_STEPS: usize = 512;
+
+//tree of shapes and the operations that combine or distort them
+#[derive(Deserialize,Serialize,Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum SdfNode {
+    Sphere { center: Vec3, radius: f64 },
+    Box { center: Vec3, size: Vec3, #[serde(default)] rounding: f64 },
+    Torus { center: Vec3, major_radius: f64, minor_radius: f64 }, //lies flat, around the y axis
+    Capsule { a: Vec3, b: Vec3, radius: f64 },
+
+    Union { objects: Vec<SdfNode> },
+    Intersection { objects: Vec<SdfNode> },
+    Subtraction { objects: Vec<SdfNode> }, //first object minus all the others
+    //smooth versions blend the surfaces together where they're closer than k
+    SmoothUnion { objects: Vec<SdfNode>, k: f64 },
+    SmoothSubtraction { objects: Vec<SdfNode>, k: f64 },
+
+    //infinitely repeat space with the given period along each axis. 0 means don't repeat
+    Repeat { period: Vec3, object: Box<SdfNode> },
+    //rotate space around the y axis by rate radians per unit of height
+    Twist { rate: f64, object: Box<SdfNode> },
+}
+impl SdfNode {
+    pub fn distance(&self, p: Vec3) -> f64 {
+        match self {
+            SdfNode::Sphere { center, radius } => (p - *center).magn() - radius,
+            SdfNode::Box { center, size, rounding } => {
+                let q = (p - *center).abs() - (*size / 2.0 - Vec3::new(*rounding, *rounding, *rounding));
+                let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magn();
+                let inside = q.x.max(q.y).max(q.z).min(0.0);
+                outside + inside - rounding
+            }
+            SdfNode::Torus { center, major_radius, minor_radius } => {
+                let q = p - *center;
+                let ring = (q.x * q.x + q.z * q.z).sqrt() - major_radius;
+                (ring * ring + q.y * q.y).sqrt() - minor_radius
+            }
+            SdfNode::Capsule { a, b, radius } => {
+                let pa = p - *a;
+                let ba = *b - *a;
+                let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+                (pa - ba * h).magn() - radius
+            }
+            SdfNode::Union { objects } => {
+                objects.iter().map(|o| o.distance(p)).fold(f64::MAX, f64::min)
+            }
+            SdfNode::Intersection { objects } => {
+                objects.iter().map(|o| o.distance(p)).fold(f64::MIN, f64::max)
+            }
+            SdfNode::Subtraction { objects } => {
+                let mut objects = objects.iter();
+                let first = objects.next().map_or(f64::MAX, |o| o.distance(p));
+                objects.fold(first, |d, o| d.max(-o.distance(p)))
+            }
+            SdfNode::SmoothUnion { objects, k } => {
+                let mut objects = objects.iter();
+                let first = objects.next().map_or(f64::MAX, |o| o.distance(p));
+                objects.fold(first, |d1, o| {
+                    let d2 = o.distance(p);
+                    let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+                    mix(d2, d1, h) - k * h * (1.0 - h)
+                })
+            }
+            SdfNode::SmoothSubtraction { objects, k } => {
+                let mut objects = objects.iter();
+                let first = objects.next().map_or(f64::MAX, |o| o.distance(p));
+                objects.fold(first, |d1, o| {
+                    let d2 = o.distance(p);
+                    let h = (0.5 - 0.5 * (d1 + d2) / k).clamp(0.0, 1.0);
+                    mix(d1, -d2, h) + k * h * (1.0 - h)
+                })
+            }
+            SdfNode::Repeat { period, object } => {
+                let wrap = |x: f64, c: f64| if c > 0.0 { x - c * (x / c).round() } else { x };
+                object.distance(Vec3::new(wrap(p.x, period.x), wrap(p.y, period.y), wrap(p.z, period.z)))
+            }
+            SdfNode::Twist { rate, object } => {
+                let (s, c) = (rate * p.y).sin_cos();
+                object.distance(Vec3::new(c * p.x - s * p.z, p.y, s * p.x + c * p.z))
+            }
+        }
+    }
+}
+fn mix(a: f64, b: f64, h: f64) -> f64 {
+    a * (1.0 - h) + b * h
+}
+
+pub struct SdfObject {
+    pub root: SdfNode,
+    pub max_distance: f64,
+    //fraction of the distance to step each time. distortions like twist make the distance
+    //function overestimate, so they need this below 1 to avoid stepping through surfaces
+    pub step_scale: f64,
+    pub material: Material,
+}
+impl SdfObject {
+    pub fn new(root: SdfNode, max_distance: f64, step_scale: f64, material: Material) -> SdfObject
+    { SdfObject { root, max_distance, step_scale, material } }
+
+    //gradient of the distance function, estimated from four samples arranged in a tetrahedron
+    fn normal(&self, p: Vec3) -> Vec3 {
+        let e = HIT_DISTANCE;
+        let k0 = Vec3::new(1.0,-1.0,-1.0);
+        let k1 = Vec3::new(-1.0,-1.0,1.0);
+        let k2 = Vec3::new(-1.0,1.0,-1.0);
+        let k3 = Vec3::new(1.0,1.0,1.0);
+        (k0 * self.root.distance(p + k0 * e) +
+         k1 * self.root.distance(p + k1 * e) +
+         k2 * self.root.distance(p + k2 * e) +
+         k3 * self.root.distance(p + k3 * e)).unit()
+    }
+}
+impl SceneObject for SdfObject {
+    fn as_any(&self) -> &dyn Any { self }
+    fn raycast(&self, ray: Ray) -> Option<RaycastHit> {
+        let dir = (ray.end - ray.start).unit();
+        //rays leaving the surface (like shadow rays) would immediately hit it again,
+        //so they have to get some distance away first
+        let min_depth = HIT_DISTANCE * 10.0;
+        let mut t = 0.0;
+        for _ in 0..MAX_STEPS {
+            let point = ray.start + dir * t;
+            let distance = self.root.distance(point).abs();
+            if distance < HIT_DISTANCE && t > min_depth {
+                let normal = self.normal(point);
+                let uv = (
+                    (normal.z.atan2(normal.x) + PI) / (2.0 * PI),
+                    normal.y.clamp(-1.0, 1.0).acos() / PI,
+                );
+                return Some(RaycastHit::new(point, normal, t, uv, self.material));
+            }
+            t += distance.max(HIT_DISTANCE) * self.step_scale;
+            if t > self.max_distance { break; }
+        }
+        None
+    }
+}
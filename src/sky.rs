@@ -0,0 +1,121 @@
+//preetham's analytic daylight model ("a practical analytic model for daylight", 1999). the
+//sky's luminance and chromaticity come from perez's formula fitted to the sun's position
+//and the turbidity (haziness) of the air, and the sun's own color from how much of its
+//light makes it through that air.
+
+use std::f64::consts::PI;
+
+use crate::*;
+
+//apparent radius of the sun in degrees
+pub const SUN_RADIUS: f64 = 0.265;
+//the sky is in kcd/m^2, this turns it into the same units as a sun of strength 1, which
+//is about 128000 lux before going through the atmosphere
+const SKY_SCALE: f64 = 1000.0 * PI / 128000.0;
+
+pub struct PhysicalSky {
+    pub elevation: f64, //degrees above the horizon
+    pub azimuth: f64, //degrees from +z toward +x
+    pub turbidity: f64, //2 is a very clear day, 10 is hazy
+    pub ground_albedo: Color,
+    pub sun: bool, //whether it lights the scene with a matching sun
+    //luminance and x, y chromaticity at the zenith, and perez coefficients for each
+    zenith: [f64;3],
+    perez: [[f64;5];3],
+    ground: Rgb,
+}
+impl PhysicalSky {
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64, ground_albedo: Color, sun: bool) -> PhysicalSky {
+        let t = turbidity;
+        //the model only goes down to the horizon
+        let theta = (90.0 - elevation).clamp(0.0, 90.0).to_radians();
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[f64;4];3]| {
+            let angles = [theta.powi(3), theta * theta, theta, 1.0];
+            let row = |r: [f64;4]| r.iter().zip(angles.iter()).map(|(a, b)| a * b).sum::<f64>();
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        let perez = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+        let mut sky = PhysicalSky {
+            elevation, azimuth, turbidity, ground_albedo, sun,
+            zenith: [luminance.max(0.0), x, y], perez, ground: Rgb::grey(0.0),
+        };
+
+        //the ground reflects whatever reaches it from the sky and sun. add up the sky over
+        //the upper hemisphere once, instead of every time the ground is looked at
+        const STEPS: usize = 32;
+        let mut irradiance = Rgb::grey(0.0);
+        let (d_theta, d_phi) = (PI / 2.0 / STEPS as f64, 2.0 * PI / (STEPS * 2) as f64);
+        for i in 0..STEPS {
+            let theta = (i as f64 + 0.5) * d_theta;
+            for j in 0..STEPS * 2 {
+                let phi = (j as f64 + 0.5) * d_phi;
+                let dir = Vec3::new(theta.sin() * phi.sin(), theta.cos(), theta.sin() * phi.cos());
+                irradiance = irradiance + sky.radiance(dir) * (theta.cos() * theta.sin() * d_theta * d_phi);
+            }
+        }
+        if sun {
+            irradiance = irradiance + sky.sun_color() * sky.sun_direction().y.max(0.0);
+        }
+        sky.ground = ground_albedo.to_rgb() * irradiance / PI;
+        sky
+    }
+    //pointing from the scene toward the sun
+    pub fn sun_direction(&self) -> Vec3 {
+        let (elevation, azimuth) = (self.elevation.to_radians(), self.azimuth.to_radians());
+        Vec3::new(elevation.cos() * azimuth.sin(), elevation.sin(), elevation.cos() * azimuth.cos())
+    }
+    //fraction of the sun's light left after going through the air, for red, green and blue
+    pub fn sun_color(&self) -> Rgb {
+        if self.elevation <= 0.0 { return Rgb::grey(0.0); }
+        let theta = 90.0 - self.elevation;
+        //how much air the light goes through compared to straight down
+        let mass = 1.0 / (theta.to_radians().cos() + 0.15 * (93.885 - theta).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |wavelength: f64| { //in micrometers
+            let rayleigh = (-0.008735 * wavelength.powf(-4.08) * mass).exp();
+            let aerosol = (-beta * wavelength.powf(-1.3) * mass).exp();
+            rayleigh * aerosol
+        };
+        Rgb::new(transmittance(0.68), transmittance(0.55), transmittance(0.44))
+    }
+    pub fn radiance(&self, dir: Vec3) -> Rgb {
+        let dir = dir.unit();
+        if dir.y < 0.0 { return self.ground; }
+        let cos_theta = dir.y.max(0.01);
+        let sun = self.sun_direction();
+        let sun_theta = (90.0 - self.elevation).clamp(0.0, 90.0).to_radians();
+        let gamma = dir.dot(sun).clamp(-1.0, 1.0).acos();
+        let perez = |c: [f64;5], theta_cos: f64, gamma: f64| {
+            (1.0 + c[0] * (c[1] / theta_cos).exp()) * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos().powi(2))
+        };
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * perez(self.perez[i], cos_theta, gamma) / perez(self.perez[i], 1.0, sun_theta)
+        });
+        if y <= 0.0 { return Rgb::grey(0.0); }
+        //xyY to XYZ, then to linear srgb
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        let rgb = Rgb::new(
+            (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+            (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+            (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+        );
+        rgb * SKY_SCALE
+    }
+}
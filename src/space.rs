@@ -1,16 +1,18 @@
 extern crate serde;
-use self::serde::Deserialize;
-use std::time::{Duration, SystemTime};
+use self::serde::{Deserialize, Serialize};
+use std::time::SystemTime;
 use std::ops::Mul;
 use std::ops::Add;
 use std::ops::Sub;
 use std::ops::Neg;
 use std::ops::Div;
 use std::fmt;
+use std::convert::TryFrom;
+use std::f64::consts::PI;
 
 
 
-#[derive(Deserialize,Clone,Copy,PartialEq)]
+#[derive(Deserialize,Serialize,Clone,Copy,PartialEq)]
 pub struct Vec3 {
     pub x: f64,
     pub y: f64,
@@ -18,7 +20,7 @@ pub struct Vec3 {
 }
 impl Vec3 {
     pub fn new(x: f64, y: f64, z: f64) -> Vec3
-    { Vec3{x: x, y: y, z: z} }
+    { Vec3{x, y, z} }
     pub fn random() -> Vec3 {
         //this really isn't all that random at all. good enough
         let seed = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_nanos();
@@ -48,6 +50,9 @@ impl Vec3 {
     pub fn unit(self) -> Vec3 {
         self/self.magn()
     }
+    pub fn abs(self) -> Vec3 {
+        Vec3::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
     pub fn to_color(self) -> Color {
         let r: u8 = (self.x * 128.0 + 128.0) as u8;
         let g: u8 = (self.y * 128.0 + 128.0) as u8;
@@ -83,6 +88,15 @@ impl Mul<f64> for Vec3 {
         Vec3::new(x,y,z)
     }
 }
+impl Div<Vec3> for Vec3 {
+    type Output = Vec3;
+    fn div(self, other: Vec3) -> Vec3 {
+        let x = self.x / other.x;
+        let y = self.y / other.y;
+        let z = self.z / other.z;
+        Vec3::new(x,y,z)
+    }
+}
 impl Div<f64> for Vec3 {
     type Output = Vec3;
     fn div(self, other: f64) -> Vec3 {
@@ -111,7 +125,59 @@ pub struct Matrix3 {
 }
 impl Matrix3 {
     pub fn new( a: Vec3, b: Vec3, c: Vec3) -> Matrix3 {
-        Matrix3 { a: a, b: b, c: c }
+        Matrix3 { a, b, c }
+    }
+    pub fn identity() -> Matrix3 {
+        Matrix3::new(Vec3::new(1.0,0.0,0.0), Vec3::new(0.0,1.0,0.0), Vec3::new(0.0,0.0,1.0))
+    }
+    pub fn transpose(self) -> Matrix3 {
+        Matrix3::new(
+            Vec3::new(self.a.x, self.b.x, self.c.x),
+            Vec3::new(self.a.y, self.b.y, self.c.y),
+            Vec3::new(self.a.z, self.b.z, self.c.z),
+        )
+    }
+    //rotation from euler angles in degrees, applied in x, y, z order
+    pub fn rotation(degrees: Vec3) -> Matrix3 {
+        let (sx, cx) = degrees.x.to_radians().sin_cos();
+        let (sy, cy) = degrees.y.to_radians().sin_cos();
+        let (sz, cz) = degrees.z.to_radians().sin_cos();
+        let rx = Matrix3::new(Vec3::new(1.0,0.0,0.0), Vec3::new(0.0,cx,sx), Vec3::new(0.0,-sx,cx));
+        let ry = Matrix3::new(Vec3::new(cy,0.0,-sy), Vec3::new(0.0,1.0,0.0), Vec3::new(sy,0.0,cy));
+        let rz = Matrix3::new(Vec3::new(cz,sz,0.0), Vec3::new(-sz,cz,0.0), Vec3::new(0.0,0.0,1.0));
+        rz * ry * rx
+    }
+    //orthonormal basis whose second column (local y) points along axis
+    pub fn from_axis(axis: Vec3) -> Matrix3 {
+        let y = axis.unit();
+        let helper = if y.x.abs() > 0.9 { Vec3::new(0.0,0.0,1.0) } else { Vec3::new(1.0,0.0,0.0) };
+        let x = y.cross(helper).unit();
+        let z = x.cross(y);
+        Matrix3::new(x,y,z)
+    }
+    //same as from_axis, but with local x pointing along tangent as closely as possible
+    pub fn from_axis_tangent(axis: Vec3, tangent: Vec3) -> Matrix3 {
+        let y = axis.unit();
+        let x = (tangent - y * tangent.dot(y)).unit();
+        let z = x.cross(y);
+        Matrix3::new(x,y,z)
+    }
+    //euler angles in degrees that rotation() would turn back into this matrix
+    pub fn euler_degrees(self) -> Vec3 {
+        let y = (-self.a.z).clamp(-1.0, 1.0).asin();
+        let (x, z) = if self.a.z.abs() < 0.999999 {
+            (self.b.z.atan2(self.c.z), self.a.y.atan2(self.a.x))
+        } else {
+            //gimbal lock, x and z rotate around the same axis so put it all in z
+            (0.0, (-self.b.x).atan2(self.b.y))
+        };
+        Vec3::new(x.to_degrees(), y.to_degrees(), z.to_degrees())
+    }
+}
+impl Mul<Matrix3> for Matrix3 {
+    type Output = Matrix3;
+    fn mul(self, other: Matrix3) -> Matrix3 {
+        Matrix3::new(self * other.a, self * other.b, self * other.c)
     }
 }
 impl Mul<Vec3> for Matrix3 {
@@ -131,7 +197,7 @@ pub struct Ray {
 }
 impl Ray {
     pub fn new(start: Vec3, end: Vec3) -> Ray
-    { Ray { start: start, end: end } }
+    { Ray { start, end } }
 }
 
 impl Sub for Vec3 {
@@ -153,7 +219,8 @@ impl Neg for Vec3 {
     }
 }
 
-#[derive(Deserialize,Clone,Copy,Debug)]
+#[derive(Deserialize,Serialize,Clone,Copy,Debug)]
+#[serde(try_from = "ColorNotation")]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -162,7 +229,48 @@ pub struct Color {
 }
 impl Color {
     pub fn new(r: u8, g: u8, b: u8, a: u8) -> Color
-    { Color{ r: r, g: g, b: b, a: a } }
+    { Color{ r, g, b, a } }
+}
+//ways a color can be written in a scene file:
+//{ "r": 255, "g": 136, "b": 0, "a": 255 } with a defaulting to 255,
+//"#ff8800" or "#f80", with optional alpha ("#ff8800ff", "#f80f"),
+//or [1.0, 0.53, 0.0] with optional alpha, each from 0 to 1
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorNotation {
+    Channels { r: u8, g: u8, b: u8, #[serde(default = "opaque")] a: u8 },
+    Hex(String),
+    Floats(Vec<f64>),
+}
+fn opaque() -> u8 { 255 }
+impl TryFrom<ColorNotation> for Color {
+    type Error = String;
+    fn try_from(notation: ColorNotation) -> Result<Color, String> {
+        match notation {
+            ColorNotation::Channels { r, g, b, a } => Ok(Color::new(r, g, b, a)),
+            ColorNotation::Hex(hex) => {
+                let digits = hex.strip_prefix('#').unwrap_or(&hex);
+                let invalid = || format!("invalid hex color \'{}\'", hex);
+                if !digits.chars().all(|c| c.is_ascii_hexdigit()) { return Err(invalid()); }
+                //short forms have one digit per channel, which gets doubled up
+                let channels: Vec<u8> = match digits.len() {
+                    3 | 4 => digits.chars().map(|c| c.to_digit(16).unwrap() as u8 * 17).collect(),
+                    6 | 8 => (0..digits.len()).step_by(2)
+                        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap()).collect(),
+                    _ => { return Err(invalid()); }
+                };
+                Ok(Color::new(channels[0], channels[1], channels[2], *channels.get(3).unwrap_or(&255)))
+            }
+            ColorNotation::Floats(floats) => {
+                if floats.len() != 3 && floats.len() != 4 {
+                    return Err(format!("float colors need 3 or 4 values, not {}", floats.len()));
+                }
+                let channel = |f: f64| (f.clamp(0.0, 1.0) * 255.0).round() as u8;
+                let a = floats.get(3).map_or(255, |&a| channel(a));
+                Ok(Color::new(channel(floats[0]), channel(floats[1]), channel(floats[2]), a))
+            }
+        }
+    }
 }
 impl Add for Color {
     type Output = Color;
@@ -177,7 +285,16 @@ impl Add for Color {
         { g = 255; }
         if b > 255
         { b = 255; }
-        Color::new(r as u8, g as u8, b as u8, a as u8)
+        Color::new(r as u8, g as u8, b as u8, a)
+    }
+}
+impl Mul<Color> for Color { //tints one color by another
+    type Output = Color;
+    fn mul(self, other: Color) -> Color {
+        let r = (self.r as u16 * other.r as u16) / 255;
+        let g = (self.g as u16 * other.g as u16) / 255;
+        let b = (self.b as u16 * other.b as u16) / 255;
+        Color::new(r as u8, g as u8, b as u8, self.a)
     }
 }
 impl Mul<f64> for Color {
@@ -202,42 +319,325 @@ pub struct Camera {
     pub direction: Vec3,
     pub length: f64,
 }
-#[derive(Deserialize)]
-pub struct World {
-    pub color: Color,
-    pub strength: f64,
+
+//linear color for amounts of light, which unlike Color can go past white
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Rgb {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+impl Rgb {
+    pub fn new(r: f64, g: f64, b: f64) -> Rgb
+    { Rgb { r, g, b } }
+    pub fn grey(v: f64) -> Rgb
+    { Rgb::new(v, v, v) }
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+    //clamped to 0..1 per channel
+    pub fn to_color(self) -> Color {
+        let channel = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Color::new(channel(self.r), channel(self.g), channel(self.b), 255)
+    }
+}
+impl Color {
+    pub fn to_rgb(self) -> Rgb {
+        Rgb::new(self.r as f64 / 255.0, self.g as f64 / 255.0, self.b as f64 / 255.0)
+    }
+}
+impl Add for Rgb {
+    type Output = Rgb;
+    fn add(self, other: Rgb) -> Rgb {
+        Rgb::new(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+impl Mul<Rgb> for Rgb {
+    type Output = Rgb;
+    fn mul(self, other: Rgb) -> Rgb {
+        Rgb::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
 }
-impl World {
-    pub fn new(color: Color, strength: f64) -> World {
-        World { color: color, strength: strength }
+impl Mul<f64> for Rgb {
+    type Output = Rgb;
+    fn mul(self, other: f64) -> Rgb {
+        Rgb::new(self.r * other, self.g * other, self.b * other)
+    }
+}
+impl Div<f64> for Rgb {
+    type Output = Rgb;
+    fn div(self, other: f64) -> Rgb {
+        self * (1.0 / other)
     }
 }
 
+//xorshift64*, small and fast. renders seed one per pixel so the same scene always
+//comes out the same, no matter how the work is split between threads
+#[derive(Clone,Copy,Debug)]
+pub struct Rng {
+    pub state: u64,
+}
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        //splitmix the seed so neighbouring seeds don't start out alike
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        Rng { state: if z == 0 { 1 } else { z } } //all zeros would stay zero forever
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+    //uniform in 0..1, never reaching 1
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+    //uniformly random unit vector
+    pub fn direction(&mut self) -> Vec3 {
+        let y = 1.0 - 2.0 * self.next_f64();
+        let r = (1.0 - y * y).sqrt();
+        let phi = 2.0 * PI * self.next_f64();
+        Vec3::new(r * phi.cos(), y, r * phi.sin())
+    }
+    //random unit vector on the side of normal, more likely the closer it is to it (with a
+    //probability density of the cosine between them over pi)
+    pub fn cosine_direction(&mut self, normal: Vec3) -> Vec3 {
+        let (u1, u2) = (self.next_f64(), self.next_f64());
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let local = Vec3::new(r * phi.cos(), (1.0 - u1).sqrt(), r * phi.sin());
+        Matrix3::from_axis(normal) * local
+    }
+}
 
-#[derive(Deserialize)]
+//fnv-1a, for hashes that have to come out the same between runs
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[derive(Deserialize,Serialize,Clone)]
 pub struct PointLight {
     pub origin: Vec3,
-    pub strength: f64,
-    pub size: f64,
+    #[serde(flatten)]
+    pub power: LightPower,
+    #[serde(default)]
+    pub size: f64, //radius for soft shadows, 0 is a true point
 }
+fn one() -> f64 { 1.0 }
 impl PointLight {
-    pub fn new(origin: Vec3, strength:f64, size:f64) -> PointLight
-    { PointLight { origin: origin, strength: strength, size: size } }
+    pub fn new(origin: Vec3, power: LightPower, size:f64) -> PointLight
+    { PointLight { origin, power, size } }
+}
+
+//how bright a light at a point is: by strength, which is just a number to turn up or down,
+//or in watts or candela. light falls off with the square of the distance, and if there's
+//a falloff_radius it also fades out smoothly to nothing there
+#[derive(Deserialize,Serialize,Clone)]
+pub struct LightPower {
+    #[serde(default = "one")]
+    pub strength: f64,
+    #[serde(default = "white")]
+    pub color: Color,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watts: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candela: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub falloff_radius: Option<f64>,
+}
+//watts are radiant, as if all of it were the green light eyes are most sensitive to
+const LUMENS_PER_WATT: f64 = 683.0;
+impl LightPower {
+    pub fn new(strength: f64, color: Color) -> LightPower {
+        LightPower { strength, color, watts: None, candela: None, falloff_radius: None }
+    }
+    //light reaching a surface facing it from distance away, where 1 lights a surface to its
+    //full color. a surface lit by one watt per square meter gives off 1/pi of it
+    pub fn irradiance(&self, distance: f64) -> Rgb {
+        let intensity = match (self.watts, self.candela) {
+            (Some(watts), _) => watts / (4.0 * PI * PI),
+            (None, Some(candela)) => candela / (LUMENS_PER_WATT * PI),
+            (None, None) => self.strength * super::EXPOSURE,
+        };
+        let mut falloff = 1.0 / (distance * distance);
+        if let Some(radius) = self.falloff_radius {
+            let window = (1.0 - (distance / radius).powi(4)).max(0.0);
+            falloff *= window * window;
+        }
+        self.color.to_rgb() * (intensity * falloff)
+    }
 }
 
 
 
-#[derive(Deserialize)]
-#[serde(untagged)]
 pub enum Light {
     Point(PointLight),
     Sun(SunLight),
+    Spot(SpotLight),
+    Ies(IesLight),
 }
-//TODO: add directional lighting
-#[derive(Deserialize)]
+//light from so far away that it comes from the same direction everywhere. a surface facing
+//a sun of strength 1 head on is lit to its full color
+#[derive(Deserialize,Serialize,Clone)]
 pub struct SunLight {
-    //direction: Vec3,
-    //strength: f64,
+    pub direction: Vec3, //from the scene toward the sun
+    #[serde(default = "one")]
+    pub strength: f64,
+    #[serde(default = "white")]
+    pub color: Color,
+    #[serde(default)]
+    pub angle: f64, //apparent diameter in degrees for soft shadows, 0 is perfectly sharp
+}
+fn white() -> Color { Color::new(255,255,255,255) }
+impl SunLight {
+    pub fn new(direction: Vec3, strength: f64, color: Color, angle: f64) -> SunLight
+    { SunLight { direction, strength, color, angle } }
+}
+
+//point light that only shines within a cone. angles are from the middle of the cone to its
+//edge in degrees, full strength inside inner_angle and fading out smoothly up to outer_angle
+#[derive(Deserialize,Serialize,Clone)]
+pub struct SpotLight {
+    pub origin: Vec3,
+    #[serde(default = "down")]
+    pub direction: Vec3,
+    #[serde(flatten)]
+    pub power: LightPower,
+    #[serde(default)]
+    pub size: f64,
+    #[serde(default = "twenty")]
+    pub inner_angle: f64,
+    #[serde(default = "thirty")]
+    pub outer_angle: f64,
+}
+fn down() -> Vec3 { Vec3::new(0.0,-1.0,0.0) }
+fn twenty() -> f64 { 20.0 }
+fn thirty() -> f64 { 30.0 }
+impl SpotLight {
+    pub fn new(origin: Vec3, direction: Vec3, power: LightPower, size: f64, inner_angle: f64, outer_angle: f64) -> SpotLight {
+        SpotLight {
+            origin, direction, power, size,
+            inner_angle, outer_angle,
+        }
+    }
+    //how much of its power goes out in direction dir
+    pub fn emission(&self, dir: Vec3) -> f64 {
+        let cos = dir.unit().dot(self.direction.unit());
+        let (inner, outer) = (self.inner_angle.to_radians().cos(), self.outer_angle.to_radians().cos());
+        if inner <= outer { return if cos >= inner { 1.0 } else { 0.0 }; }
+        let t = ((cos - outer) / (inner - outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t) //smoothstep
+    }
+}
+
+//point light shaped by a measured light distribution from an ies file
+pub struct IesLight {
+    pub origin: Vec3,
+    pub direction: Vec3, //where the fixture points, the profile's vertical angle of 0
+    pub rotation: f64, //degrees the profile is turned around direction
+    pub power: LightPower, //watts or candela are for the profile's brightest direction
+    pub size: f64,
+    pub profile: IesProfile,
+    pub scale: f64, //what the profile's candela are multiplied by to get its emission
+}
+impl IesLight {
+    pub fn new(origin: Vec3, direction: Vec3, rotation: f64, power: LightPower, size: f64, profile: IesProfile) -> IesLight {
+        //without watts or candela the light is as bright as the fixture that was measured, times
+        //its strength. profiles that give off no light at all stay dark either way
+        let scale = if power.watts.is_none() && power.candela.is_none() {
+            1.0 / (LUMENS_PER_WATT * PI * super::EXPOSURE)
+        } else if profile.max_candela > 0.0 {
+            1.0 / profile.max_candela
+        } else {
+            0.0
+        };
+        IesLight {
+            origin, direction, rotation, power, size, profile, scale,
+        }
+    }
+    pub fn emission(&self, dir: Vec3) -> f64 {
+        //local y is the fixture's direction, horizontal angles go from local x toward local z
+        let local = Matrix3::from_axis(self.direction).transpose() * dir.unit();
+        let vertical = local.y.clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = local.z.atan2(local.x).to_degrees() - self.rotation;
+        self.profile.candela(vertical, horizontal.rem_euclid(360.0)) * self.scale
+    }
+}
+
+//candela for a grid of angles, type c photometry: vertical angles from straight down (0) to
+//straight up (180), horizontal ones around the fixture
+pub struct IesProfile {
+    pub vertical: Vec<f64>,
+    pub horizontal: Vec<f64>,
+    pub candela: Vec<f64>, //vertical.len() values for each horizontal angle
+    pub max_candela: f64,
+    pub source: Option<std::path::PathBuf>, //None for profiles built in code
+}
+impl IesProfile {
+    pub fn new(vertical: Vec<f64>, horizontal: Vec<f64>, candela: Vec<f64>) -> IesProfile {
+        let max_candela = candela.iter().cloned().fold(0.0, f64::max);
+        IesProfile { vertical, horizontal, candela, max_candela, source: None }
+    }
+    //interpolated candela at angles in degrees. profiles only cover as much as they need to,
+    //the rest is mirrored from what's there
+    pub fn candela(&self, vertical: f64, horizontal: f64) -> f64 {
+        let last = *self.horizontal.last().unwrap();
+        let horizontal = if last <= 0.0 {
+            0.0 //the same all the way around
+        } else if last <= 90.0 {
+            let h = horizontal % 180.0; //mirrored in both halves
+            if h > 90.0 { 180.0 - h } else { h }
+        } else if last <= 180.0 {
+            if horizontal > 180.0 { 360.0 - horizontal } else { horizontal } //mirrored left to right
+        } else {
+            horizontal
+        };
+        //index of the angle at or below a, and how far a is toward the next one
+        let find = |angles: &[f64], a: f64| -> Option<(usize, f64)> {
+            if a < angles[0] || a > angles[angles.len() - 1] { return None; }
+            let i = angles.partition_point(|&b| b <= a).clamp(1, angles.len()) - 1;
+            if i + 1 == angles.len() { return Some((i, 0.0)); }
+            let span = angles[i + 1] - angles[i];
+            Some((i, if span > 0.0 { (a - angles[i]) / span } else { 0.0 }))
+        };
+        let (v, fv) = match find(&self.vertical, vertical) { Some(found) => found, None => { return 0.0; } };
+        let (h, fh) = find(&self.horizontal, horizontal).unwrap_or((self.horizontal.len() - 1, 0.0));
+        let rows = self.vertical.len();
+        let at = |h: usize, v: usize| self.candela[h.min(self.horizontal.len() - 1) * rows + v.min(rows - 1)];
+        let near = at(h, v) * (1.0 - fv) + at(h, v + 1) * fv;
+        let far = at(h + 1, v) * (1.0 - fv) + at(h + 1, v + 1) * fv;
+        near * (1.0 - fh) + far * fh
+    }
+}
+
+//soft light from everywhere at once, standing in for light bouncing around the scene. it's
+//dimmed by how much of the surroundings within radius are blocked (ambient occlusion)
+#[derive(Deserialize,Serialize,Clone)]
+pub struct Ambient {
+    #[serde(default = "white")]
+    pub color: Color,
+    #[serde(default = "tenth")]
+    pub strength: f64,
+    #[serde(default = "one")]
+    pub radius: f64, //how far away things still block it
+    #[serde(default = "sixteen")]
+    pub samples: usize, //rays each diffuse hit sends to find what's around it
+}
+fn tenth() -> f64 { 0.1 }
+fn sixteen() -> usize { 16 }
+impl Ambient {
+    pub fn new(color: Color, strength: f64, radius: f64, samples: usize) -> Ambient {
+        Ambient { color, strength, radius, samples }
+    }
 }
 #[derive(Deserialize)]
 pub struct Tri {
@@ -250,13 +650,13 @@ impl Tri
     pub fn new (
         a: Vec3,    b: Vec3,    c: Vec3, vn_a: Vec3, vn_b: Vec3, vn_c: Vec3, material: Material
     ) -> Tri { 
-        Tri { verts: ( a, b, c ), vx_normals: ( vn_a, vn_b, vn_c ), material: material}
+        Tri { verts: ( a, b, c ), vx_normals: ( vn_a, vn_b, vn_c ), material}
     }
     pub fn auto_normal(a: Vec3, b: Vec3, c: Vec3, material: Material) -> Tri { 
         let edge0 = b - a;
         let edge1 = c - a;
         let normal = edge0.cross(edge1).unit();
-        Tri { verts: ( a, b, c ), vx_normals: (normal, normal, normal), material: material}
+        Tri { verts: ( a, b, c ), vx_normals: (normal, normal, normal), material}
     }
 }
 
@@ -269,7 +669,7 @@ pub struct Sphere {
 }
 impl Sphere {
     pub fn new(center: Vec3, radius: f64, material: Material) -> Sphere
-    { Sphere { center: center, radius: radius, material: material } }
+    { Sphere { center, radius, material } }
 }
 
 
@@ -277,10 +677,62 @@ impl Sphere {
 pub struct Material {
     pub color: Color,
     pub reflective: bool,
-}
+    #[serde(default = "black")]
+    pub specular: Color, //color of highlights, black for none
+    #[serde(default = "half")]
+    pub roughness: f64, //0 is a perfectly sharp highlight, 1 spreads it out all over
+    //how much light goes through, tinted by the color. 0 is opaque. only shadows go through
+    //for now, the surface itself is still drawn solid
+    #[serde(default)]
+    pub transparency: f64,
+}
+fn black() -> Color { Color::new(0,0,0,255) }
+fn half() -> f64 { 0.5 }
 impl Material {
     pub fn new(color: Color, reflective: bool) -> Material {
-        Material { color: color, reflective: reflective }
+        Material { color, reflective, specular: black(), roughness: half(), transparency: 0.0 }
+    }
+    pub fn with_specular(self, specular: Color, roughness: f64) -> Material {
+        Material { specular, roughness, ..self }
+    }
+    pub fn with_transparency(self, transparency: f64) -> Material {
+        Material { transparency, ..self }
+    }
+    //light let through by one surface of this material
+    pub fn transmittance(&self) -> Rgb {
+        self.color.to_rgb() * self.transparency.clamp(0.0, 1.0)
+    }
+    //the same for materials that are the same, every time. never 0
+    pub fn id(&self) -> u32 {
+        let mut bytes = vec![self.color.r, self.color.g, self.color.b, self.color.a, self.reflective as u8];
+        bytes.extend_from_slice(&[self.specular.r, self.specular.g, self.specular.b, self.specular.a]);
+        bytes.extend_from_slice(&self.roughness.to_bits().to_le_bytes());
+        bytes.extend_from_slice(&self.transparency.to_bits().to_le_bytes());
+        let hash = fnv1a(&bytes);
+        ((hash ^ (hash >> 32)) as u32).max(1)
+    }
+    //roughness for a blinn-phong shininess exponent, matching the width of the highlight
+    pub fn shininess_roughness(shininess: f64) -> f64 {
+        (2.0 / (shininess.max(0.0) + 2.0)).sqrt().sqrt()
+    }
+    //ggx microfacet highlight of light from to_light seen from to_viewer. like the diffuse
+    //color, this is how much of the light reaching the surface is reflected toward the viewer
+    pub fn highlight(&self, normal: Vec3, to_light: Vec3, to_viewer: Vec3) -> Rgb {
+        let specular = self.specular.to_rgb();
+        if specular == Rgb::grey(0.0) { return specular; }
+        let (nl, nv) = (normal.dot(to_light), normal.dot(to_viewer));
+        if nl <= 0.0 || nv <= 0.0 { return Rgb::grey(0.0); }
+        let half = (to_light + to_viewer).unit();
+        let (nh, vh) = (normal.dot(half).max(0.0), to_viewer.dot(half).max(0.0));
+        //very small roughnesses make highlights too small to ever be hit
+        let alpha = self.roughness.clamp(0.02, 1.0).powi(2);
+        let alpha2 = alpha * alpha;
+        let distribution = alpha2 / (PI * (nh * nh * (alpha2 - 1.0) + 1.0).powi(2));
+        let k = alpha / 2.0;
+        let geometry = nl / (nl * (1.0 - k) + k) * nv / (nv * (1.0 - k) + k);
+        //schlick's fresnel, which brightens highlights toward white at grazing angles
+        let fresnel = specular + (Rgb::grey(1.0) + specular * -1.0) * (1.0 - vh).powi(5);
+        fresnel * (distribution * geometry / (4.0 * nl * nv) * PI)
     }
 }
 
@@ -290,21 +742,24 @@ pub struct RaycastHit {
     pub point: Vec3,
     pub normal: Vec3,
     pub depth: f64,
+    pub uv: (f64,f64),
     pub material: Material,
 }
 impl RaycastHit {
-    pub fn new(point: Vec3, normal: Vec3, depth: f64, material: Material) -> RaycastHit
-    { RaycastHit { point: point, normal: normal, depth: depth, material: material } }
+    pub fn new(point: Vec3, normal: Vec3, depth: f64, uv: (f64,f64), material: Material) -> RaycastHit
+    { RaycastHit { point, normal, depth, uv, material } }
 }
 
-#[derive(Deserialize)]
-pub struct Floor {
-    pub y: f64,
-    pub material: Material
+//a stretch of a ray spent inside of a closed object, from where it enters to where it leaves.
+//depths can be negative if the stretch starts behind the ray
+#[derive(Clone,Copy)]
+pub struct Interval {
+    pub enter: RaycastHit,
+    pub exit: RaycastHit,
 }
-impl Floor {
-    pub fn new(y: f64, material: Material) -> Floor
-    { Floor { y: y, material: material } }
+impl Interval {
+    pub fn new(enter: RaycastHit, exit: RaycastHit) -> Interval
+    { Interval { enter, exit } }
 }
 
 
@@ -0,0 +1,132 @@
+//the image is rendered in square tiles, so the rays a thread traces at once stay close together,
+//handed out in an order that decides which parts of the image show up first.
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum TileOrder {
+    Scanline, //rows from the top, left to right
+    Hilbert, //along a hilbert curve, where every tile is next to the one before it
+    Spiral, //outwards from the center, where the subject usually is
+}
+pub const TILE_ORDERS: [TileOrder;3] = [TileOrder::Scanline, TileOrder::Hilbert, TileOrder::Spiral];
+impl TileOrder {
+    pub fn name(self) -> &'static str {
+        match self {
+            TileOrder::Scanline => "scanline",
+            TileOrder::Hilbert => "hilbert",
+            TileOrder::Spiral => "spiral",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<TileOrder> {
+        TILE_ORDERS.iter().copied().find(|order| order.name() == name)
+    }
+}
+
+//a rectangle of pixels. tiles along the right and bottom edges are cut short by the image
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+impl Tile {
+    pub fn pixels(&self) -> usize {
+        self.width * self.height
+    }
+    //index into the image of the i'th pixel of the tile, going row by row
+    pub fn pixel(&self, i: usize, image_width: usize) -> usize {
+        (self.y + i / self.width) * image_width + self.x + i % self.width
+    }
+}
+
+//every tile of a width by height image, in the order they should be rendered
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+    let tile = |(column, row): (usize, usize)| {
+        let (x, y) = (column * size, row * size);
+        Tile { x, y, width: size.min(width - x), height: size.min(height - y) }
+    };
+    let grid = (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row)));
+    match order {
+        TileOrder::Scanline => grid.map(tile).collect(),
+        TileOrder::Hilbert => {
+            //the curve only fills powers of two, so it's made big enough and whatever falls
+            //outside the image is skipped
+            let side = columns.max(rows).next_power_of_two();
+            (0..side * side).map(|d| hilbert_point(side, d))
+                .filter(|(column, row)| *column < columns && *row < rows).map(tile).collect()
+        }
+        TileOrder::Spiral => {
+            //ring by ring around the center, going around each one by angle
+            let center = (columns as f64 / 2.0, rows as f64 / 2.0);
+            let mut grid: Vec<(usize, usize)> = grid.collect();
+            let key = |(column, row): &(usize, usize)| {
+                let (dx, dy) = (*column as f64 + 0.5 - center.0, *row as f64 + 0.5 - center.1);
+                (dx.abs().max(dy.abs()).floor(), dy.atan2(dx))
+            };
+            grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+            grid.into_iter().map(tile).collect()
+        }
+    }
+}
+
+//the point d steps along a hilbert curve filling a side by side square
+fn hilbert_point(side: usize, d: usize) -> (usize, usize) {
+    let (mut x, mut y, mut d) = (0, 0, d);
+    let mut s = 1;
+    while s < side {
+        let rx = 1 & (d / 2);
+        let ry = 1 & (d ^ rx);
+        if ry == 0 { //rotate the quadrant so the curve lines up
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        d /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_order_covers_each_pixel_once() {
+        //square, wide, tall, one tile, uneven edges and smaller than a tile
+        for (width, height, size) in [(64, 64, 16), (100, 30, 16), (7, 50, 4), (16, 16, 16), (33, 17, 8), (5, 3, 16), (1, 1, 1)] {
+            let scanline = tiles(width, height, size, TileOrder::Scanline);
+            for order in TILE_ORDERS {
+                let tiles = tiles(width, height, size, order);
+                assert_eq!(tiles.len(), scanline.len(), "{} {}x{}", order.name(), width, height);
+                let mut covered = vec![0; width * height];
+                for tile in &tiles {
+                    assert!(tile.width > 0 && tile.height > 0 && tile.width <= size && tile.height <= size);
+                    for i in 0..tile.pixels() { covered[tile.pixel(i, width)] += 1; }
+                }
+                assert!(covered.iter().all(|count| *count == 1), "{} {}x{}", order.name(), width, height);
+            }
+        }
+    }
+
+    #[test]
+    fn hilbert_tiles_follow_each_other() {
+        //on a power of two grid the curve never jumps
+        let tiles = tiles(128, 128, 16, TileOrder::Hilbert);
+        for pair in tiles.windows(2) {
+            let (dx, dy) = (pair[0].x.abs_diff(pair[1].x), pair[0].y.abs_diff(pair[1].y));
+            assert_eq!(dx + dy, 16);
+        }
+    }
+
+    #[test]
+    fn spiral_starts_in_the_middle() {
+        let first = tiles(64, 64, 16, TileOrder::Spiral)[0];
+        assert!((16..48).contains(&first.x) && (16..48).contains(&first.y));
+    }
+}
@@ -0,0 +1,161 @@
+//everything around the scene that rays can escape to. besides showing up behind objects and
+//in reflections, skies and environment maps light diffuse surfaces from every direction,
+//with rays spent where the sky is brightest.
+
+use std::path::PathBuf;
+use std::f64::consts::PI;
+
+use crate::*;
+
+pub enum Sky {
+    Color(Color), //plain background, seen behind things but doesn't light the scene
+    Gradient { zenith: Color, horizon: Color, ground: Color },
+    Image(EnvMap),
+    Physical(PhysicalSky),
+}
+
+pub struct World {
+    pub sky: Sky,
+    pub rotation: f64, //degrees around the y axis
+    pub strength: f64,
+    pub samples: usize, //rays each diffuse hit sends toward the sky
+}
+impl World {
+    pub fn new(sky: Sky, rotation: f64, strength: f64, samples: usize) -> World {
+        World { sky, rotation, strength, samples }
+    }
+    pub fn color(color: Color) -> World {
+        World::new(Sky::Color(color), 0.0, 1.0, 0)
+    }
+    pub fn lights_scene(&self) -> bool {
+        !matches!(self.sky, Sky::Color(_)) && self.samples > 0 && self.strength > 0.0
+    }
+    fn rotation_matrix(&self) -> Matrix3 {
+        Matrix3::rotation(Vec3::new(0.0, self.rotation, 0.0))
+    }
+    //light coming from direction dir
+    pub fn radiance(&self, dir: Vec3) -> Rgb {
+        let dir = self.rotation_matrix().transpose() * dir.unit();
+        let light = match &self.sky {
+            Sky::Color(color) => color.to_rgb(),
+            Sky::Gradient { zenith, horizon, ground } => {
+                //blends by angle from the horizon, toward the zenith above and the ground below
+                let t = dir.y.clamp(-1.0, 1.0).asin().abs() / (PI / 2.0);
+                let end = if dir.y >= 0.0 { zenith } else { ground };
+                horizon.to_rgb() * (1.0 - t) + end.to_rgb() * t
+            }
+            Sky::Image(map) => map.sample(direction_uv(dir)),
+            Sky::Physical(sky) => sky.radiance(dir),
+        };
+        light * self.strength
+    }
+    //what a ray that escapes the scene sees, which unlike radiance includes the disk of a
+    //physical sky's sun. that light is already given by the sun itself
+    pub fn background(&self, dir: Vec3) -> Rgb {
+        let light = self.radiance(dir);
+        match self.sun() {
+            Some(sun) if dir.unit().dot(sun.direction) > SUN_RADIUS.to_radians().cos() => {
+                let solid_angle = PI * SUN_RADIUS.to_radians().powi(2);
+                light + sun.color.to_rgb() * (sun.strength / solid_angle)
+            }
+            _ => light,
+        }
+    }
+    //the sun that goes along with a physical sky, turned with the rest of it
+    pub fn sun(&self) -> Option<SunLight> {
+        match &self.sky {
+            Sky::Physical(sky) if sky.sun && sky.elevation > 0.0 => {
+                let direction = self.rotation_matrix() * sky.sun_direction();
+                Some(SunLight::new(direction, self.strength, sky.sun_color().to_color(), SUN_RADIUS * 2.0))
+            }
+            _ => None,
+        }
+    }
+    //direction to gather light from at a surface facing normal, and the probability density of
+    //picking it. maps are sampled by brightness, anything else by the cosine around the normal
+    pub fn sample(&self, normal: Vec3, rng: &mut Rng) -> (Vec3, f64) {
+        match &self.sky {
+            Sky::Image(map) => {
+                let (uv, density) = map.pick(rng);
+                let sin_theta = (uv.1 * PI).sin();
+                if sin_theta <= 0.0 { return (normal, 0.0); }
+                let dir = self.rotation_matrix() * uv_direction(uv);
+                (dir, density / (2.0 * PI * PI * sin_theta))
+            }
+            _ => {
+                let dir = rng.cosine_direction(normal);
+                (dir, dir.dot(normal).max(0.0) / PI)
+            }
+        }
+    }
+}
+
+//equirectangular: u goes once around the horizon starting and ending behind -z, v from
+//straight up at the top row to straight down at the bottom
+fn direction_uv(dir: Vec3) -> (f64,f64) {
+    (0.5 + dir.x.atan2(-dir.z) / (2.0 * PI), dir.y.clamp(-1.0, 1.0).acos() / PI)
+}
+fn uv_direction(uv: (f64,f64)) -> Vec3 {
+    let (sin_phi, cos_phi) = ((uv.0 - 0.5) * 2.0 * PI).sin_cos();
+    let (sin_theta, cos_theta) = (uv.1 * PI).sin_cos();
+    Vec3::new(sin_theta * sin_phi, cos_theta, -sin_theta * cos_phi)
+}
+
+//equirectangular image of the light around the scene, usually hdr
+pub struct EnvMap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+    pub source: Option<PathBuf>, //None for maps built in code
+    //cumulative distributions for picking texels by brightness. rows has height + 1 entries,
+    //columns has width + 1 for each row, and all of them go from 0 to 1
+    rows: Vec<f64>,
+    columns: Vec<f64>,
+}
+impl EnvMap {
+    pub fn new(width: usize, height: usize, pixels: Vec<Rgb>) -> EnvMap {
+        let average = pixels.iter().map(|p| p.luminance().max(0.0)).sum::<f64>() / pixels.len().max(1) as f64;
+        let mut rows = vec![0.0];
+        let mut columns = Vec::with_capacity((width + 1) * height);
+        for y in 0..height {
+            //rows near the poles cover less of the sphere. the small constant keeps every
+            //texel pickable, even black ones
+            let sin_theta = ((y as f64 + 0.5) / height as f64 * PI).sin();
+            let mut sum = 0.0;
+            columns.push(0.0);
+            for x in 0..width {
+                sum += (pixels[y * width + x].luminance().max(0.0) + average * 0.01 + 1e-9) * sin_theta;
+                columns.push(sum);
+            }
+            for c in &mut columns[y * (width + 1)..] { *c /= sum; }
+            rows.push(rows[y] + sum);
+        }
+        let total = rows[height];
+        for r in &mut rows { *r /= total; }
+        EnvMap { width, height, pixels, source: None, rows, columns }
+    }
+    //bilinear filtered, wrapping around horizontally
+    pub fn sample(&self, uv: (f64,f64)) -> Rgb {
+        let x = uv.0 * self.width as f64 - 0.5;
+        let y = (uv.1 * self.height as f64 - 0.5).clamp(0.0, (self.height - 1) as f64);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let texel = |x: f64, y: f64| {
+            let x = (x as i64).rem_euclid(self.width as i64) as usize;
+            let y = (y as usize).min(self.height - 1);
+            self.pixels[y * self.width + x]
+        };
+        texel(x0, y0) * ((1.0 - fx) * (1.0 - fy)) + texel(x0 + 1.0, y0) * (fx * (1.0 - fy)) +
+        texel(x0, y0 + 1.0) * ((1.0 - fx) * fy) + texel(x0 + 1.0, y0 + 1.0) * (fx * fy)
+    }
+    //random uv, more likely in brighter texels, and the density it was picked with
+    fn pick(&self, rng: &mut Rng) -> ((f64,f64), f64) {
+        let find = |cdf: &[f64], u: f64| cdf.partition_point(|&c| c <= u).clamp(1, cdf.len() - 1) - 1;
+        let y = find(&self.rows, rng.next_f64());
+        let row = &self.columns[y * (self.width + 1)..(y + 1) * (self.width + 1)];
+        let x = find(row, rng.next_f64());
+        let chance = (self.rows[y + 1] - self.rows[y]) * (row[x + 1] - row[x]);
+        let uv = ((x as f64 + rng.next_f64()) / self.width as f64, (y as f64 + rng.next_f64()) / self.height as f64);
+        (uv, chance * (self.width * self.height) as f64)
+    }
+}